@@ -0,0 +1,42 @@
+// Rough throughput numbers for decode and the interpreter loop, run with `cargo bench`.
+// Uses plain Instant timing so there's no extra dependency
+use std::hint::black_box;
+use std::time::Instant;
+
+use chip8_core::{assemble, decode, Emu};
+
+const CYCLES: usize = 5_000_000;
+
+fn main() {
+    let start = Instant::now();
+    for op in (0..=0xFFFF_u16).cycle().take(CYCLES) {
+        black_box(decode(black_box(op)));
+    }
+    report("decode", start);
+
+    // a tight loop touching the ALU, memory and the draw path
+    let rom = assemble(
+        "
+        LD V0, 0x01
+        ADD V1, V0
+        XOR V2, V1
+        SHR V3, V2
+        LD I, 0x300
+        LD [I], V3
+        DRW V0, V1, 4
+        JMP 0x202
+        ",
+    )
+    .unwrap();
+    let mut emu = Emu::with_seed(1);
+    emu.load_rom(&rom).unwrap();
+    let start = Instant::now();
+    emu.tick_n(CYCLES).unwrap();
+    report("tick", start);
+}
+
+fn report(name: &str, start: Instant) {
+    let elapsed = start.elapsed();
+    let per_op = elapsed.as_nanos() as f64 / CYCLES as f64;
+    println!("{:<8} {:>10.2?} total, {:.2} ns each", name, elapsed, per_op);
+}
@@ -0,0 +1,111 @@
+; Flag test for the 8XYN arithmetic, assembled by the harness tests.
+; Every check draws an A in a row across the top of the screen when both the result and VF are
+; right, and an E when either is wrong, then the ROM exits with 00FD.
+; A check is: VA = E, SNE VF, flag (skip unless VF is right), VA = A,
+; SE result, value (skip if the result is right), VA = E, then draw glyph VA.
+
+        LD V8, 0        ; glyph x
+        LD V9, 0        ; glyph y
+
+; 8XY4 without carry: 0x10 + 0x20 = 0x30, VF = 0
+        LD V1, 0x10
+        LD V2, 0x20
+        ADD V1, V2
+        LD VA, 0xE
+        SNE VF, 0
+        LD VA, 0xA
+        SE V1, 0x30
+        LD VA, 0xE
+        LD F, VA
+        DRW V8, V9, 5
+        ADD V8, 5
+
+; 8XY4 with carry: 0xFF + 0x02 = 0x01, VF = 1
+        LD V1, 0xFF
+        LD V2, 0x02
+        ADD V1, V2
+        LD VA, 0xE
+        SNE VF, 1
+        LD VA, 0xA
+        SE V1, 0x01
+        LD VA, 0xE
+        LD F, VA
+        DRW V8, V9, 5
+        ADD V8, 5
+
+; 8XY5 without borrow: 0x30 - 0x10 = 0x20, VF = 1
+        LD V1, 0x30
+        LD V2, 0x10
+        SUB V1, V2
+        LD VA, 0xE
+        SNE VF, 1
+        LD VA, 0xA
+        SE V1, 0x20
+        LD VA, 0xE
+        LD F, VA
+        DRW V8, V9, 5
+        ADD V8, 5
+
+; 8XY5 with borrow: 0x10 - 0x30 = 0xE0, VF = 0
+        LD V1, 0x10
+        LD V2, 0x30
+        SUB V1, V2
+        LD VA, 0xE
+        SNE VF, 0
+        LD VA, 0xA
+        SE V1, 0xE0
+        LD VA, 0xE
+        LD F, VA
+        DRW V8, V9, 5
+        ADD V8, 5
+
+; 8XY7: 0x30 - 0x10 = 0x20, VF = 1
+        LD V1, 0x10
+        LD V2, 0x30
+        SUBN V1, V2
+        LD VA, 0xE
+        SNE VF, 1
+        LD VA, 0xA
+        SE V1, 0x20
+        LD VA, 0xE
+        LD F, VA
+        DRW V8, V9, 5
+        ADD V8, 5
+
+; 8XY6: 0x05 >> 1 = 0x02, VF = 1. VX and VY match so shift_quirk doesn't matter
+        LD V1, 0x05
+        LD V2, 0x05
+        SHR V1, V2
+        LD VA, 0xE
+        SNE VF, 1
+        LD VA, 0xA
+        SE V1, 0x02
+        LD VA, 0xE
+        LD F, VA
+        DRW V8, V9, 5
+        ADD V8, 5
+
+; 8XYE: 0x81 << 1 = 0x02, VF = 1
+        LD V1, 0x81
+        LD V2, 0x81
+        SHL V1, V2
+        LD VA, 0xE
+        SNE VF, 1
+        LD VA, 0xA
+        SE V1, 0x02
+        LD VA, 0xE
+        LD F, VA
+        DRW V8, V9, 5
+        ADD V8, 5
+
+; VF as the destination keeps the flag, not the sum: 0xFF + 0x03 leaves VF = 1, not 0x02
+        LD VF, 0xFF
+        LD V2, 0x03
+        ADD VF, V2
+        LD VA, 0xE
+        SNE VF, 1
+        LD VA, 0xA
+        LD F, VA
+        DRW V8, V9, 5
+
+        EXIT
@@ -0,0 +1,235 @@
+// A small assembler for the same syntax disassemble prints, mostly so tests and tiny programs
+// can be written as text instead of hand-encoded hex. One instruction per line, `;` starts a
+// comment, mnemonics and registers are case-insensitive and numbers are decimal or 0x hex.
+// Registers are V0 - V15 as disassemble writes them, VA - VF work too. `DB 0xXXXX` emits a
+// raw word
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Why assemble rejected a line, `line` counts from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmError {
+    // Not an instruction assemble knows
+    UnknownMnemonic { line: usize },
+    // Known instruction with the wrong number or kind of operands
+    BadOperands { line: usize },
+    // Number too big for its field, e.g. a 12-bit address over 0xFFF
+    OutOfRange { line: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(u16),
+    Num(u16),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+}
+
+// Assembles `src` into big-endian opcodes ready for load_rom
+pub fn assemble(src: &str) -> Result<Vec<u8>, AsmError> {
+    let mut out = Vec::new();
+    for (idx, line) in src.lines().enumerate() {
+        let line_no = idx + 1;
+        let code = line.split(';').next().unwrap_or("").trim();
+        if code.is_empty() {
+            continue;
+        }
+        let op = assemble_line(code).map_err(|kind| kind.at(line_no))?;
+        out.extend_from_slice(&op.to_be_bytes());
+    }
+    Ok(out)
+}
+
+// Error kind before the line number is known
+enum LineError {
+    UnknownMnemonic,
+    BadOperands,
+    OutOfRange,
+}
+
+impl LineError {
+    fn at(self, line: usize) -> AsmError {
+        match self {
+            LineError::UnknownMnemonic => AsmError::UnknownMnemonic { line },
+            LineError::BadOperands => AsmError::BadOperands { line },
+            LineError::OutOfRange => AsmError::OutOfRange { line },
+        }
+    }
+}
+
+fn assemble_line(code: &str) -> Result<u16, LineError> {
+    let (mnemonic, rest) = match code.find(char::is_whitespace) {
+        Some(split) => code.split_at(split),
+        None => (code, ""),
+    };
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    let operands = if rest.trim().is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',').map(|token| parse_operand(token.trim())).collect::<Result<Vec<_>, _>>()?
+    };
+
+    use Operand::*;
+    let op = match (mnemonic.as_str(), operands.as_slice()) {
+        ("NOP", []) => 0x0000,
+        ("SCD", &[Num(n)]) => 0x00C0 | nibble(n)?,
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("SYS", &[Num(nnn)]) => addr(nnn)?,
+        ("JMP", &[Num(nnn)]) => 0x1000 | addr(nnn)?,
+        ("JMP", &[V(0), Num(nnn)]) => 0xB000 | addr(nnn)?,
+        ("CALL", &[Num(nnn)]) => 0x2000 | addr(nnn)?,
+        ("SE", &[V(x), Num(nn)]) => 0x3000 | x << 8 | byte(nn)?,
+        ("SE", &[V(x), V(y)]) => 0x5000 | x << 8 | y << 4,
+        ("SNE", &[V(x), Num(nn)]) => 0x4000 | x << 8 | byte(nn)?,
+        ("SNE", &[V(x), V(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", &[V(x), Num(nn)]) => 0x6000 | x << 8 | byte(nn)?,
+        ("LD", &[V(x), V(y)]) => 0x8000 | x << 8 | y << 4,
+        ("LD", &[I, Num(nnn)]) => 0xA000 | addr(nnn)?,
+        ("LD", &[V(x), Dt]) => 0xF007 | x << 8,
+        ("LD", &[V(x), K]) => 0xF00A | x << 8,
+        ("LD", &[Dt, V(x)]) => 0xF015 | x << 8,
+        ("LD", &[St, V(x)]) => 0xF018 | x << 8,
+        ("LD", &[F, V(x)]) => 0xF029 | x << 8,
+        ("LD", &[Hf, V(x)]) => 0xF030 | x << 8,
+        ("LD", &[B, V(x)]) => 0xF033 | x << 8,
+        ("LD", &[IndirectI, V(x)]) => 0xF055 | x << 8,
+        ("LD", &[V(x), IndirectI]) => 0xF065 | x << 8,
+        ("LD", &[R, V(x)]) => 0xF075 | x << 8,
+        ("LD", &[V(x), R]) => 0xF085 | x << 8,
+        ("ADD", &[V(x), Num(nn)]) => 0x7000 | x << 8 | byte(nn)?,
+        ("ADD", &[V(x), V(y)]) => 0x8004 | x << 8 | y << 4,
+        ("ADD", &[I, V(x)]) => 0xF01E | x << 8,
+        ("OR", &[V(x), V(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", &[V(x), V(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", &[V(x), V(y)]) => 0x8003 | x << 8 | y << 4,
+        ("SUB", &[V(x), V(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", &[V(x), V(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SUBN", &[V(x), V(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", &[V(x), V(y)]) => 0x800E | x << 8 | y << 4,
+        ("RND", &[V(x), Num(nn)]) => 0xC000 | x << 8 | byte(nn)?,
+        ("DRW", &[V(x), V(y), Num(n)]) => 0xD000 | x << 8 | y << 4 | nibble(n)?,
+        ("SKP", &[V(x)]) => 0xE09E | x << 8,
+        ("SKNP", &[V(x)]) => 0xE0A1 | x << 8,
+        ("PLANE", &[Num(n)]) => 0xF001 | nibble(n)? << 8,
+        ("DB", &[Num(word)]) => word,
+        (
+            "NOP" | "SCD" | "CLS" | "RET" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "SYS"
+            | "JMP" | "CALL" | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND" | "XOR" | "SUB"
+            | "SHR" | "SUBN" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP" | "PLANE" | "DB",
+            _,
+        ) => return Err(LineError::BadOperands),
+        _ => return Err(LineError::UnknownMnemonic),
+    };
+    Ok(op)
+}
+
+fn parse_operand(token: &str) -> Result<Operand, LineError> {
+    let upper: String = token.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        reg if reg.starts_with('V') => {
+            let digits = &reg[1..];
+            // V0 - V15 in decimal like disassemble, or a single hex digit for VA - VF
+            let idx = digits.parse::<u16>().ok().filter(|&idx| idx < 16);
+            let idx = idx.or_else(|| match digits.len() {
+                1 => u16::from_str_radix(digits, 16).ok(),
+                _ => None,
+            });
+            Operand::V(idx.ok_or(LineError::BadOperands)?)
+        }
+        num => Operand::Num(parse_number(num)?),
+    };
+    Ok(operand)
+}
+
+fn parse_number(token: &str) -> Result<u16, LineError> {
+    let parsed = match token.strip_prefix("0X") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => token.parse::<u32>(),
+    };
+    let value = parsed.map_err(|_| LineError::BadOperands)?;
+    u16::try_from(value).map_err(|_| LineError::OutOfRange)
+}
+
+fn nibble(n: u16) -> Result<u16, LineError> {
+    if n <= 0xF { Ok(n) } else { Err(LineError::OutOfRange) }
+}
+
+fn byte(nn: u16) -> Result<u16, LineError> {
+    if nn <= 0xFF { Ok(nn) } else { Err(LineError::OutOfRange) }
+}
+
+fn addr(nnn: u16) -> Result<u16, LineError> {
+    if nnn <= 0xFFF { Ok(nnn) } else { Err(LineError::OutOfRange) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{disassemble, Emu};
+
+    #[test]
+    fn round_trips_every_word() {
+        for op in 0..=0xFFFF_u16 {
+            let text = disassemble(op);
+            assert_eq!(assemble(&text), Ok(op.to_be_bytes().to_vec()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn assemble_program() {
+        let src = "
+            ; draw the 0 glyph in the top left, then spin
+            ld v0, 0x00
+            LD F, V0
+            drw V0, V0, 5   ; 5 rows
+            JMP 0x206
+        ";
+        let rom = assemble(src).unwrap();
+        assert_eq!(rom, [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06]);
+
+        let mut emu = Emu::new();
+        emu.load_rom(&rom).unwrap();
+        let listing: Vec<String> =
+            emu.disassemble_range(0x200, rom.len()).into_iter().map(|(_, _, text)| text).collect();
+        assert_eq!(listing, ["LD V0, 0x00", "LD F, V0", "DRW V0, V0, 5", "JMP 0x206"]);
+    }
+
+    #[test]
+    fn hex_register_names() {
+        assert_eq!(assemble("ADD VA, VF"), Ok(vec![0x8A, 0xF4]));
+        assert_eq!(assemble("LD V12, 12"), Ok(vec![0x6C, 0x0C]));
+    }
+
+    #[test]
+    fn reports_errors_with_line() {
+        assert_eq!(assemble("CLS\nFOO V0"), Err(AsmError::UnknownMnemonic { line: 2 }));
+        assert_eq!(assemble("LD V0"), Err(AsmError::BadOperands { line: 1 }));
+        assert_eq!(assemble("LD V16, 1"), Err(AsmError::BadOperands { line: 1 }));
+        assert_eq!(assemble("JMP V1, 0x200"), Err(AsmError::BadOperands { line: 1 }));
+        assert_eq!(assemble("\n\nJMP 0x1000"), Err(AsmError::OutOfRange { line: 3 }));
+        assert_eq!(assemble("LD V0, 256"), Err(AsmError::OutOfRange { line: 1 }));
+        assert_eq!(assemble("DB 0x10000"), Err(AsmError::OutOfRange { line: 1 }));
+    }
+}
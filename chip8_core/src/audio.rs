@@ -0,0 +1,135 @@
+// Square wave beep for frontends that want samples to hand to an audio backend rather than
+// polling is_beeping
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::Emu;
+
+// Beep pitch until set_beep_frequency is called
+pub const DEFAULT_BEEP_HZ: f32 = 440.0;
+// Peak amplitude of the square wave, kept well under 1.0 so it isn't harsh
+const BEEP_VOLUME: f32 = 0.25;
+
+impl Emu {
+    // Sets the beep pitch in Hz. Zero, negative, infinite and NaN pitches are ignored and the
+    // previous one is kept
+    pub fn set_beep_frequency(&mut self, hz: f32) {
+        if hz.is_finite() && hz > 0.0 {
+            self.beep_hz = hz;
+        }
+    }
+
+    // Installs a callback that tick_timers calls with true when the sound timer starts a beep
+    // and false once it runs out, for audio backends that would rather not poll is_beeping
+    pub fn set_sound_callback(&mut self, f: Box<dyn FnMut(bool)>) {
+        self.sound_callback = Some(f);
+    }
+
+    pub fn clear_sound_callback(&mut self) {
+        self.sound_callback = None;
+    }
+
+    // Next `num_samples` mono samples at `sample_rate`, a square wave while the sound timer is
+    // running and silence otherwise. The wave's phase carries over between calls so back to
+    // back buffers join up without clicks
+    pub fn audio_samples(&mut self, sample_rate: u32, num_samples: usize) -> Vec<f32> {
+        if !self.is_beeping() || sample_rate == 0 {
+            return vec![0.0; num_samples];
+        }
+        let step = self.beep_hz / sample_rate as f32;
+        (0..num_samples)
+            .map(|_| {
+                let sample = if self.beep_phase < 0.5 { BEEP_VOLUME } else { -BEEP_VOLUME };
+                // % keeps the phase in 0..1 in one go even when a step is many cycles long
+                self.beep_phase = (self.beep_phase + step) % 1.0;
+                sample
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn silent_unless_beeping() {
+        let mut emu = Emu::new();
+        assert!(emu.audio_samples(44_100, 512).iter().all(|&s| s == 0.0));
+        emu.st = 10;
+        let samples = emu.audio_samples(44_100, 512);
+        assert_eq!(samples.len(), 512);
+        assert!(samples.iter().all(|&s| s != 0.0));
+        assert!(samples.iter().any(|&s| s > 0.0) && samples.iter().any(|&s| s < 0.0));
+    }
+
+    #[test]
+    fn square_wave_frequency() {
+        let mut emu = Emu::new();
+        emu.st = 10;
+        emu.set_beep_frequency(1000.0);
+        // 8 samples per cycle, half high and half low
+        let samples = emu.audio_samples(8000, 16);
+        let high = [BEEP_VOLUME; 4];
+        let low = [-BEEP_VOLUME; 4];
+        assert_eq!(samples, [high, low, high, low].concat());
+    }
+
+    #[test]
+    fn bad_frequencies_are_ignored() {
+        let mut emu = Emu::new();
+        emu.st = 10;
+        emu.set_beep_frequency(1000.0);
+        for hz in [0.0, -440.0, f32::INFINITY, f32::NEG_INFINITY, f32::NAN] {
+            emu.set_beep_frequency(hz);
+        }
+        assert_eq!(emu.beep_hz, 1000.0);
+        assert_eq!(emu.audio_samples(8000, 4), [BEEP_VOLUME; 4]);
+    }
+
+    #[test]
+    fn huge_frequency_keeps_phase_in_range() {
+        let mut emu = Emu::new();
+        emu.st = 10;
+        emu.set_beep_frequency(f32::MAX);
+        assert_eq!(emu.audio_samples(44_100, 64).len(), 64);
+        assert!((0.0..1.0).contains(&emu.beep_phase));
+    }
+
+    #[test]
+    fn phase_carries_across_calls() {
+        let mut split = Emu::new();
+        let mut whole = Emu::new();
+        split.st = 10;
+        whole.st = 10;
+        let mut samples = split.audio_samples(44_100, 100);
+        samples.extend(split.audio_samples(44_100, 157));
+        assert_eq!(samples, whole.audio_samples(44_100, 257));
+    }
+
+    #[test]
+    fn sound_callback_on_start_and_stop() {
+        let mut emu = Emu::new();
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let log = calls.clone();
+        emu.set_sound_callback(Box::new(move |on| log.borrow_mut().push(on)));
+
+        emu.tick_timers();
+        assert!(calls.borrow().is_empty());
+        // LD V0, 2 / LD ST, V0
+        emu.execute(0x6002).unwrap();
+        emu.execute(0xF018).unwrap();
+        emu.tick_timers();
+        assert_eq!(*calls.borrow(), [true]);
+        emu.tick_timers();
+        assert_eq!(*calls.borrow(), [true]);
+        // st is 0 now, the next tick ends the beep
+        emu.tick_timers();
+        assert_eq!(*calls.borrow(), [true, false]);
+        emu.tick_timers();
+        assert_eq!(*calls.borrow(), [true, false]);
+    }
+}
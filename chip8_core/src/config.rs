@@ -0,0 +1,202 @@
+use crate::{DEFAULT_PALETTE, RAM_SIZE, START_ADDR};
+
+// Interpreter quirks, timing and memory layout. CHIP-8 implementations disagree on a handful of
+// opcodes so these pick which behavior Emu follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chip8Config {
+    // Shift quirk - when true 8XY6/8XYE shift VX in place (CHIP-48/SCHIP), when false VY is
+    // shifted into VX (COSMAC VIP)
+    pub shift_quirk: bool,
+    // Jump quirk - when true BXNN jumps to XNN + VX (CHIP-48/SCHIP), when false BNNN jumps to
+    // NNN + V0 (COSMAC VIP)
+    pub jump_quirk: bool,
+    // Clip sprites - when true sprite pixels past the right/bottom edge are dropped, when false
+    // they wrap around to the other side
+    pub clip_sprites: bool,
+    // I overflow quirk - when true FX1E sets VF if I goes past 0x0FFF (Amiga CHIP-8)
+    pub i_overflow_quirk: bool,
+    // Load/store quirk - when true FX55/FX65 leave I pointing past the last register
+    // (COSMAC VIP), when false I is left unchanged (SCHIP)
+    pub load_store_increments_i: bool,
+    // VF reset quirk - when true 8XY1/8XY2/8XY3 clear VF (COSMAC VIP)
+    pub vf_reset_quirk: bool,
+    // Display wait quirk - when true DXYN waits for the next 60Hz frame before drawing, so
+    // there's at most one draw per frame (COSMAC VIP)
+    pub display_wait_quirk: bool,
+    // Whether the delay and sound timers stop with the CPU while Emu is paused. Some games
+    // resume oddly if their timers ran out during a pause
+    pub freeze_timers_on_pause: bool,
+    // Number of instructions to run for every 60Hz frame
+    pub ticks_per_frame: usize,
+    // Bytes of RAM, 4KB for CHIP-8/SCHIP and 64KB for XO-CHIP. Must be able to hold both fonts
+    // (240 bytes) and no more than 64KB
+    pub ram_size: usize,
+    // Where load_rom puts the program and execution starts
+    pub start_addr: u16,
+    // What to do on a word that isn't an instruction
+    pub on_unknown_opcode: UnknownOpcode,
+    // What to do on 0NNN, a call into COSMAC VIP machine code that can't be emulated. Error
+    // returns Chip8Error::UnsupportedMachineCall
+    pub on_machine_call: UnknownOpcode,
+    // RGBA colors by XO-CHIP color index for frontends and screenshots, see DEFAULT_PALETTE
+    pub palette: [[u8; 4]; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnknownOpcode {
+    // Panic straight away, handy when debugging the interpreter itself
+    Panic,
+    // Skip over it and carry on with the next instruction
+    Nop,
+    // tick returns Chip8Error::UnknownOpcode with the word
+    Error,
+}
+
+impl Chip8Config {
+    // The original COSMAC VIP interpreter
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_quirk: false,
+            jump_quirk: false,
+            clip_sprites: true,
+            i_overflow_quirk: false,
+            load_store_increments_i: true,
+            vf_reset_quirk: true,
+            display_wait_quirk: true,
+            freeze_timers_on_pause: true,
+            ticks_per_frame: 10,
+            ram_size: RAM_SIZE,
+            start_addr: START_ADDR,
+            on_unknown_opcode: UnknownOpcode::Error,
+            on_machine_call: UnknownOpcode::Error,
+            palette: DEFAULT_PALETTE,
+        }
+    }
+
+    // SUPER-CHIP 1.1 on the HP48
+    pub fn schip() -> Self {
+        Self {
+            shift_quirk: true,
+            jump_quirk: true,
+            clip_sprites: true,
+            i_overflow_quirk: false,
+            load_store_increments_i: false,
+            vf_reset_quirk: false,
+            display_wait_quirk: false,
+            freeze_timers_on_pause: true,
+            ticks_per_frame: 30,
+            ram_size: RAM_SIZE,
+            start_addr: START_ADDR,
+            on_unknown_opcode: UnknownOpcode::Error,
+            on_machine_call: UnknownOpcode::Error,
+            palette: DEFAULT_PALETTE,
+        }
+    }
+}
+
+// Modern interpreter behavior - shift in place, classic jump, I incremented by load/store
+impl Default for Chip8Config {
+    fn default() -> Self {
+        Self {
+            shift_quirk: true,
+            jump_quirk: false,
+            clip_sprites: true,
+            i_overflow_quirk: false,
+            load_store_increments_i: true,
+            vf_reset_quirk: false,
+            display_wait_quirk: false,
+            freeze_timers_on_pause: true,
+            ticks_per_frame: 10,
+            ram_size: RAM_SIZE,
+            start_addr: START_ADDR,
+            on_unknown_opcode: UnknownOpcode::Error,
+            on_machine_call: UnknownOpcode::Error,
+            palette: DEFAULT_PALETTE,
+        }
+    }
+}
+
+// Best guess at the config a ROM was written for, from the opcodes it contains. Only the
+// instruction-aligned words are scanned, so sprite data can cause false hits and code reached
+// at odd addresses is missed. Any SCHIP-only instruction (00CN, 00FB - 00FF, DXY0, FX30,
+// FX75, FX85) picks the SCHIP preset. Failing that, an 8XY6/8XYE with X != Y only makes sense
+// if VY is shifted, so it picks the COSMAC VIP preset. Everything else gets the default
+pub fn detect_quirks(rom: &[u8]) -> Chip8Config {
+    let ops = rom.chunks_exact(2).map(|word| (word[0] as u16) << 8 | word[1] as u16);
+    let mut shifts_vy = false;
+    for op in ops {
+        let (x, y, n) = ((op >> 8) & 0xF, (op >> 4) & 0xF, op & 0xF);
+        let schip_only = match op >> 12 {
+            0x0 => op & 0xFFF0 == 0x00C0 || (0x00FB..=0x00FF).contains(&op),
+            0xD => n == 0,
+            0xF => matches!(op & 0xFF, 0x30 | 0x75 | 0x85),
+            _ => false,
+        };
+        if schip_only {
+            return Chip8Config::schip();
+        }
+        if op >> 12 == 0x8 && (n == 0x6 || n == 0xE) && x != y {
+            shifts_vy = true;
+        }
+    }
+    if shifts_vy {
+        Chip8Config::cosmac_vip()
+    } else {
+        Chip8Config::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cosmac_vip_preset() {
+        let config = Chip8Config::cosmac_vip();
+        assert!(!config.shift_quirk);
+        assert!(!config.jump_quirk);
+        assert!(config.clip_sprites);
+        assert!(!config.i_overflow_quirk);
+        assert!(config.load_store_increments_i);
+        assert!(config.vf_reset_quirk);
+        assert!(config.display_wait_quirk);
+    }
+
+    #[test]
+    fn schip_preset() {
+        let config = Chip8Config::schip();
+        assert!(config.shift_quirk);
+        assert!(config.jump_quirk);
+        assert!(config.clip_sprites);
+        assert!(!config.i_overflow_quirk);
+        assert!(!config.load_store_increments_i);
+        assert!(!config.vf_reset_quirk);
+        assert!(!config.display_wait_quirk);
+    }
+
+    #[test]
+    fn detect_schip_rom() {
+        // CLS / HIGH / LD V0, 3 / LD HF, V0 / DRW V1, V2, 0
+        let rom = [0x00, 0xE0, 0x00, 0xFF, 0x60, 0x03, 0xF0, 0x30, 0xD1, 0x20];
+        assert_eq!(detect_quirks(&rom), Chip8Config::schip());
+        // a scroll alone is enough
+        assert_eq!(detect_quirks(&[0x00, 0xC4]), Chip8Config::schip());
+    }
+
+    #[test]
+    fn detect_vy_shift() {
+        // SHR V1, V2 / JMP 0x200
+        assert_eq!(detect_quirks(&[0x81, 0x26, 0x12, 0x00]), Chip8Config::cosmac_vip());
+        // shifting in place works either way
+        assert_eq!(detect_quirks(&[0x81, 0x1E, 0x12, 0x00]), Chip8Config::default());
+    }
+
+    #[test]
+    fn detect_plain_rom() {
+        assert_eq!(detect_quirks(&[]), Chip8Config::default());
+        // LD V0, 0xFF / JMP 0x200 - 0x30 and 0xFF as the low byte of other opcodes don't count
+        assert_eq!(detect_quirks(&[0x60, 0xFF, 0x12, 0x30]), Chip8Config::default());
+    }
+}
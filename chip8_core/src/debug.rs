@@ -0,0 +1,363 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::{Chip8Error, Emu};
+
+// How many return addresses dump_state shows
+const DUMP_STACK_DEPTH: usize = 4;
+
+// Why run_until_break handed control back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    // PC landed on a breakpoint, holding its address
+    Breakpoint(u16),
+    // max_cycles instructions ran without reaching a breakpoint
+    CycleLimit,
+}
+
+impl Emu {
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    // Installs a hook called with (pc, opcode) right after each instruction is fetched and
+    // before it runs, replacing any previous hook
+    pub fn set_trace_hook(&mut self, f: Box<dyn FnMut(u16, u16)>) {
+        self.trace_hook = Some(f);
+    }
+
+    pub fn clear_trace_hook(&mut self) {
+        self.trace_hook = None;
+    }
+
+    // Calls `callback` with (address, new value) whenever an opcode writes to `addr`,
+    // replacing any previous watch on that address
+    pub fn watch_address(&mut self, addr: u16, callback: Box<dyn FnMut(u16, u8)>) {
+        self.watchpoints.insert(addr, callback);
+    }
+
+    pub fn unwatch_address(&mut self, addr: u16) {
+        self.watchpoints.remove(&addr);
+    }
+
+    // Return addresses of the active subroutine calls, outermost first
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    // The registers, timers and the innermost return addresses as a block of text to print
+    // when a ROM misbehaves. The layout stays the same so tests can compare against it:
+    //   PC=0x200 I=0x000 SP=0 DT=0 ST=0
+    //   V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00
+    //   V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=00
+    //   STACK=-
+    // STACK lists up to DUMP_STACK_DEPTH addresses innermost first, with ... when there are more
+    pub fn dump_state(&self) -> String {
+        let mut out = String::with_capacity(160);
+        // writing to a String can't fail
+        let _ = writeln!(
+            out,
+            "PC={:#05X} I={:#05X} SP={} DT={} ST={}",
+            self.pc, self.i_reg, self.sp, self.dt, self.st
+        );
+        for (offset, regs) in self.v_reg.chunks(8).enumerate() {
+            for (idx, value) in regs.iter().enumerate() {
+                let sep = if idx == 0 { "" } else { " " };
+                let _ = write!(out, "{}V{:X}={:02X}", sep, offset * 8 + idx, value);
+            }
+            out.push('\n');
+        }
+        out.push_str("STACK=");
+        let stack = self.call_stack();
+        if stack.is_empty() {
+            out.push('-');
+        }
+        for (idx, addr) in stack.iter().rev().take(DUMP_STACK_DEPTH).enumerate() {
+            let sep = if idx == 0 { "" } else { " " };
+            let _ = write!(out, "{}{:#05X}", sep, addr);
+        }
+        if stack.len() > DUMP_STACK_DEPTH {
+            out.push_str(" ...");
+        }
+        out.push('\n');
+        out
+    }
+
+    // true when the instruction at PC is a 1NNN jump to itself, the usual way a program ends.
+    // Frontends can stop burning CPU on it
+    pub fn is_spinning(&self) -> bool {
+        let op = self.peek_opcode();
+        op & 0xF000 == 0x1000 && op & 0xFFF == self.pc
+    }
+
+    // Turns the opcode profiler on or off. Turning it on starts the counts from scratch, while
+    // it's off tick skips the bookkeeping entirely
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+        if enabled {
+            self.opcode_histogram.clear();
+        }
+    }
+
+    // How many times each full opcode has executed since profiling was turned on
+    pub fn opcode_histogram(&self) -> &BTreeMap<u16, u64> {
+        &self.opcode_histogram
+    }
+
+    // Debugger step over - a 2NNN CALL runs until its subroutine returns, anything else is a
+    // single tick. Breakpoints inside the subroutine stop it early, a subroutine that never
+    // returns keeps it running forever
+    pub fn step_over(&mut self) -> Result<(), Chip8Error> {
+        let pc = self.pc as usize;
+        let is_call = self.ram.get(pc).is_some_and(|&hi| hi & 0xF0 == 0x20);
+        let depth = self.sp;
+        self.tick()?;
+        if is_call {
+            while self.sp > depth && !self.breakpoints.contains(&self.pc) {
+                self.tick()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Runs up to n instructions, stopping early once 00FD halts or PC lands on a breakpoint,
+    // and returns how many ran. An error comes back with the count that ran before it
+    pub fn tick_n(&mut self, n: usize) -> Result<usize, (usize, Chip8Error)> {
+        for count in 0..n {
+            self.tick().map_err(|err| (count, err))?;
+            if self.halted || self.breakpoints.contains(&self.pc) {
+                return Ok(count + 1);
+            }
+        }
+        Ok(n)
+    }
+
+    // Ticks until PC reaches a breakpoint or max_cycles instructions have run. At least one
+    // instruction runs before breakpoints are checked, so resuming from a breakpoint moves on
+    pub fn run_until_break(&mut self, max_cycles: usize) -> Result<StopReason, Chip8Error> {
+        for _ in 0..max_cycles {
+            self.tick()?;
+            if self.breakpoints.contains(&self.pc) {
+                return Ok(StopReason::Breakpoint(self.pc));
+            }
+        }
+        Ok(StopReason::CycleLimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn dump_fresh_state() {
+        let emu = Emu::new();
+        assert_eq!(
+            emu.dump_state(),
+            "PC=0x200 I=0x000 SP=0 DT=0 ST=0\n\
+             V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00\n\
+             V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=00\n\
+             STACK=-\n"
+        );
+    }
+
+    #[test]
+    fn dump_shows_innermost_calls() {
+        let mut emu = Emu::new();
+        for addr in [0x300, 0x310, 0x320, 0x330, 0x340] {
+            emu.execute(0x2000 | addr).unwrap();
+        }
+        emu.v_reg[0xA] = 0xBC;
+        emu.i_reg = 0xFFF;
+        let dump = emu.dump_state();
+        assert!(dump.starts_with("PC=0x340 I=0xFFF SP=5 "));
+        assert!(dump.contains(" VA=BC "));
+        assert!(dump.ends_with("STACK=0x330 0x320 0x310 0x300 ...\n"));
+    }
+
+    #[test]
+    fn stops_at_breakpoint() {
+        let mut emu = Emu::new();
+        // LD V0, 1 / ADD V0, 1 / ADD V0, 1 / JMP 0x200
+        emu.load_rom(&[0x60, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x00]).unwrap();
+        emu.add_breakpoint(0x204);
+        assert_eq!(emu.run_until_break(100), Ok(StopReason::Breakpoint(0x204)));
+        assert_eq!(emu.pc(), 0x204);
+        assert_eq!(emu.v_reg(0), 2);
+
+        // resuming runs past the breakpoint and around the loop back to it
+        assert_eq!(emu.run_until_break(100), Ok(StopReason::Breakpoint(0x204)));
+        assert_eq!(emu.v_reg(0), 2);
+
+        emu.remove_breakpoint(0x204);
+        assert_eq!(emu.run_until_break(8), Ok(StopReason::CycleLimit));
+    }
+
+    #[test]
+    fn run_until_break_reports_errors() {
+        let mut emu = Emu::new();
+        // RET with nothing on the stack
+        emu.load_rom(&[0x00, 0xEE]).unwrap();
+        assert_eq!(emu.run_until_break(10), Err(Chip8Error::StackUnderflow));
+    }
+
+    #[test]
+    fn trace_hook_sees_every_instruction() {
+        let mut emu = Emu::new();
+        // LD V0, 1 / CALL 0x208 / NOP / NOP / RET
+        emu.load_rom(&[0x60, 0x01, 0x22, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0xEE]).unwrap();
+        let trace = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&trace);
+        emu.set_trace_hook(Box::new(move |pc, op| sink.borrow_mut().push((pc, op))));
+        for _ in 0..4 {
+            emu.tick().unwrap();
+        }
+        assert_eq!(
+            *trace.borrow(),
+            vec![(0x200, 0x6001), (0x202, 0x2208), (0x208, 0x00EE), (0x204, 0x0000)]
+        );
+
+        emu.clear_trace_hook();
+        emu.tick().unwrap();
+        assert_eq!(trace.borrow().len(), 4);
+    }
+
+    #[test]
+    fn watchpoint_fires_on_write() {
+        let mut emu = Emu::new();
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&writes);
+        emu.watch_address(0x301, Box::new(move |addr, val| sink.borrow_mut().push((addr, val))));
+        emu.v_reg[0] = 156;
+        emu.i_reg = 0x300;
+        emu.execute(0xF033).unwrap();
+        assert_eq!(*writes.borrow(), vec![(0x301, 5)]);
+
+        // FX55 writes through the same path
+        emu.v_reg[1] = 0x42;
+        emu.execute(0xF155).unwrap();
+        assert_eq!(*writes.borrow(), vec![(0x301, 5), (0x301, 0x42)]);
+
+        emu.unwatch_address(0x301);
+        emu.i_reg = 0x300;
+        emu.execute(0xF033).unwrap();
+        assert_eq!(writes.borrow().len(), 2);
+    }
+
+    #[test]
+    fn detects_jump_to_self() {
+        let mut emu = Emu::new();
+        // LD V0, 1 / JMP 0x202
+        emu.load_rom(&[0x60, 0x01, 0x12, 0x02]).unwrap();
+        assert!(!emu.is_spinning());
+        emu.tick().unwrap();
+        assert!(emu.is_spinning());
+        emu.tick().unwrap();
+        assert!(emu.is_spinning());
+        assert_eq!(emu.pc(), 0x202);
+
+        // a jump anywhere else isn't a spin
+        emu.load_rom(&[0x12, 0x00, 0x12, 0x00]).unwrap();
+        assert!(!emu.is_spinning());
+        emu.pc = 0xFFF;
+        assert!(!emu.is_spinning());
+    }
+
+    #[test]
+    fn step_over_runs_through_call() {
+        let mut emu = Emu::new();
+        // CALL 0x206 / LD V1, 2 / JMP 0x204 / ADD V0, 1 / CALL 0x20C / RET / ADD V0, 1 / RET
+        emu.load_rom(&[
+            0x22, 0x06, 0x61, 0x02, 0x12, 0x04, 0x70, 0x01, 0x22, 0x0C, 0x00, 0xEE, 0x70, 0x01,
+            0x00, 0xEE,
+        ])
+        .unwrap();
+        emu.step_over().unwrap();
+        assert_eq!(emu.pc(), 0x202);
+        assert_eq!(emu.sp(), 0);
+        assert_eq!(emu.v_reg(0), 2);
+
+        // anything other than a call is a single step
+        emu.step_over().unwrap();
+        assert_eq!(emu.pc(), 0x204);
+        assert_eq!(emu.v_reg(1), 2);
+    }
+
+    #[test]
+    fn step_over_stops_at_breakpoint() {
+        let mut emu = Emu::new();
+        // CALL 0x204 / NOP / ADD V0, 1 / RET
+        emu.load_rom(&[0x22, 0x04, 0x00, 0x00, 0x70, 0x01, 0x00, 0xEE]).unwrap();
+        emu.add_breakpoint(0x206);
+        emu.step_over().unwrap();
+        assert_eq!(emu.pc(), 0x206);
+        assert_eq!(emu.sp(), 1);
+    }
+
+    #[test]
+    fn call_stack_lists_return_addresses() {
+        let mut emu = Emu::new();
+        // CALL 0x204 / NOP / CALL 0x208 / NOP / RET
+        emu.load_rom(&[0x22, 0x04, 0x00, 0x00, 0x22, 0x08, 0x00, 0x00, 0x00, 0xEE]).unwrap();
+        assert!(emu.call_stack().is_empty());
+        emu.tick().unwrap();
+        emu.tick().unwrap();
+        assert_eq!(emu.call_stack(), &[0x202, 0x206]);
+        emu.tick().unwrap();
+        assert_eq!(emu.call_stack(), &[0x202]);
+    }
+
+    #[test]
+    fn tick_n_stops_early() {
+        let mut emu = Emu::new();
+        // LD V0, 1 / ADD V0, 1 / EXIT / ADD V0, 1
+        emu.load_rom(&[0x60, 0x01, 0x70, 0x01, 0x00, 0xFD, 0x70, 0x01]).unwrap();
+        assert_eq!(emu.tick_n(100), Ok(3));
+        assert!(emu.is_halted());
+        assert_eq!(emu.v_reg(0), 2);
+        assert_eq!(emu.tick_n(100), Err((0, Chip8Error::Halted)));
+
+        emu.reset();
+        emu.load_rom(&[0x60, 0x01, 0x70, 0x01, 0x00, 0xFD]).unwrap();
+        assert_eq!(emu.tick_n(1), Ok(1));
+        emu.add_breakpoint(0x204);
+        assert_eq!(emu.tick_n(100), Ok(1));
+        assert_eq!(emu.pc(), 0x204);
+    }
+
+    #[test]
+    fn tick_n_reports_count_with_error() {
+        let mut emu = Emu::new();
+        // LD V0, 1 / LD V1, 1 / RET with nothing on the stack
+        emu.load_rom(&[0x60, 0x01, 0x61, 0x01, 0x00, 0xEE]).unwrap();
+        assert_eq!(emu.tick_n(10), Err((2, Chip8Error::StackUnderflow)));
+    }
+
+    #[test]
+    fn profiles_opcodes() {
+        let mut emu = Emu::new();
+        // ADD V0, 1 / SE V0, 3 / JMP 0x200 / JMP 0x206
+        emu.load_rom(&[0x70, 0x01, 0x30, 0x03, 0x12, 0x00, 0x12, 0x06]).unwrap();
+        emu.tick_n(3).unwrap();
+        assert!(emu.opcode_histogram().is_empty());
+
+        emu.set_profiling(true);
+        emu.tick_n(7).unwrap();
+        let counts: Vec<(u16, u64)> =
+            emu.opcode_histogram().iter().map(|(&op, &n)| (op, n)).collect();
+        assert_eq!(counts, vec![(0x1200, 1), (0x1206, 2), (0x3003, 2), (0x7001, 2)]);
+
+        emu.set_profiling(false);
+        emu.tick_n(5).unwrap();
+        assert_eq!(emu.opcode_histogram()[&0x1206], 2);
+    }
+}
@@ -0,0 +1,275 @@
+// Splits a raw opcode into an Instruction once so Emu::execute and disassemble both match on
+// the same decoded form instead of each picking the nibbles apart themselves
+
+// One decoded opcode. x and y are register indices, n is the low nibble, nn the low byte and nnn
+// the low 12 bits. Words that aren't a known instruction decode to Unknown with the raw word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    // 0000
+    Nop,
+    // 00CN (SCHIP)
+    ScrollDown { n: u8 },
+    // 00E0
+    Cls,
+    // 00EE
+    Ret,
+    // 00FB (SCHIP)
+    ScrollRight,
+    // 00FC (SCHIP)
+    ScrollLeft,
+    // 00FD (SCHIP)
+    Exit,
+    // 00FE (SCHIP)
+    LoRes,
+    // 00FF (SCHIP)
+    HiRes,
+    // 0NNN, a call into COSMAC VIP machine code
+    MachineCall { nnn: u16 },
+    // 1NNN
+    Jump { nnn: u16 },
+    // 2NNN
+    Call { nnn: u16 },
+    // 3XNN
+    SkipEqByte { x: usize, nn: u8 },
+    // 4XNN
+    SkipNeByte { x: usize, nn: u8 },
+    // 5XY0
+    SkipEqReg { x: usize, y: usize },
+    // 6XNN
+    LoadByte { x: usize, nn: u8 },
+    // 7XNN
+    AddByte { x: usize, nn: u8 },
+    // 8XY0
+    LoadReg { x: usize, y: usize },
+    // 8XY1
+    Or { x: usize, y: usize },
+    // 8XY2
+    And { x: usize, y: usize },
+    // 8XY3
+    Xor { x: usize, y: usize },
+    // 8XY4
+    AddReg { x: usize, y: usize },
+    // 8XY5
+    Sub { x: usize, y: usize },
+    // 8XY6
+    Shr { x: usize, y: usize },
+    // 8XY7
+    SubN { x: usize, y: usize },
+    // 8XYE
+    Shl { x: usize, y: usize },
+    // 9XY0
+    SkipNeReg { x: usize, y: usize },
+    // ANNN
+    LoadI { nnn: u16 },
+    // BNNN, x is kept for the SCHIP BXNN jump quirk
+    JumpOffset { x: usize, nnn: u16 },
+    // CXNN
+    Random { x: usize, nn: u8 },
+    // DXYN
+    Draw { x: usize, y: usize, n: u8 },
+    // EX9E
+    SkipKey { x: usize },
+    // EXA1
+    SkipNotKey { x: usize },
+    // FN01 (XO-CHIP)
+    Plane { mask: u8 },
+    // FX07
+    LoadDelay { x: usize },
+    // FX0A
+    WaitKey { x: usize },
+    // FX15
+    SetDelay { x: usize },
+    // FX18
+    SetSound { x: usize },
+    // FX1E
+    AddI { x: usize },
+    // FX29
+    Font { x: usize },
+    // FX30 (SCHIP)
+    BigFont { x: usize },
+    // FX33
+    Bcd { x: usize },
+    // FX55
+    Store { x: usize },
+    // FX65
+    Load { x: usize },
+    // FX75 (SCHIP)
+    StoreFlags { x: usize },
+    // FX85 (SCHIP)
+    LoadFlags { x: usize },
+    Unknown(u16),
+}
+
+// Every opcode pattern decode recognizes, in the order decode matches them. X and Y stand for
+// register nibbles and N for any other operand nibble, so DXYN covers DXY0 too
+const IMPLEMENTED_OPCODES: [&str; 46] = [
+    "0000", "00CN", "00E0", "00EE", "00FB", "00FC", "00FD", "00FE", "00FF", "0NNN", "1NNN",
+    "2NNN", "3XNN", "4XNN", "5XY0", "6XNN", "7XNN", "8XY0", "8XY1", "8XY2", "8XY3", "8XY4",
+    "8XY5", "8XY6", "8XY7", "8XYE", "9XY0", "ANNN", "BNNN", "CXNN", "DXYN", "EX9E", "EXA1",
+    "FN01", "FX07", "FX0A", "FX15", "FX18", "FX1E", "FX29", "FX30", "FX33", "FX55", "FX65",
+    "FX75", "FX85",
+];
+
+// The opcode patterns the interpreter supports, so it's easy to see what's still missing.
+// Anything else decodes to Instruction::Unknown
+pub fn implemented_opcodes() -> &'static [&'static str] {
+    &IMPLEMENTED_OPCODES
+}
+
+pub fn decode(op: u16) -> Instruction {
+    // (from left to right)
+    // we mask the nibble at the position we want then shift all bits over all bits by a number
+    // that will place that nibble in the first four positions
+    // the last digit doesn't need a shift since it's already in the desired position
+    let digit1 = (op & 0xF000) >> 12;
+    let digit2 = (op & 0x0F00) >> 8;
+    let digit3 = (op & 0x00F0) >> 4;
+    let digit4 = op & 0x000F;
+
+    let x = digit2 as usize;
+    let y = digit3 as usize;
+    let n = digit4 as u8;
+    let nn = (op & 0xFF) as u8;
+    // 0xFFF gets us the lower 12 bits
+    // e.g.
+    /*
+          1010101111001101 (0xABCD)
+       &  0000111111111111 (0xFFF)
+          ----------------------
+          0000101111001101 (0x0BCD)
+    */
+    let nnn = op & 0xFFF;
+
+    use Instruction::*;
+    match (digit1, digit2, digit3, digit4) {
+        (0, 0, 0, 0) => Nop,
+        (0, 0, 0xC, _) => ScrollDown { n },
+        (0, 0, 0xE, 0) => Cls,
+        (0, 0, 0xE, 0xE) => Ret,
+        (0, 0, 0xF, 0xB) => ScrollRight,
+        (0, 0, 0xF, 0xC) => ScrollLeft,
+        (0, 0, 0xF, 0xD) => Exit,
+        (0, 0, 0xF, 0xE) => LoRes,
+        (0, 0, 0xF, 0xF) => HiRes,
+        (0, _, _, _) => MachineCall { nnn },
+        (1, _, _, _) => Jump { nnn },
+        (2, _, _, _) => Call { nnn },
+        (3, _, _, _) => SkipEqByte { x, nn },
+        (4, _, _, _) => SkipNeByte { x, nn },
+        (5, _, _, 0) => SkipEqReg { x, y },
+        (6, _, _, _) => LoadByte { x, nn },
+        (7, _, _, _) => AddByte { x, nn },
+        (8, _, _, 0) => LoadReg { x, y },
+        (8, _, _, 1) => Or { x, y },
+        (8, _, _, 2) => And { x, y },
+        (8, _, _, 3) => Xor { x, y },
+        (8, _, _, 4) => AddReg { x, y },
+        (8, _, _, 5) => Sub { x, y },
+        (8, _, _, 6) => Shr { x, y },
+        (8, _, _, 7) => SubN { x, y },
+        (8, _, _, 0xE) => Shl { x, y },
+        (9, _, _, 0) => SkipNeReg { x, y },
+        (0xA, _, _, _) => LoadI { nnn },
+        (0xB, _, _, _) => JumpOffset { x, nnn },
+        (0xC, _, _, _) => Random { x, nn },
+        (0xD, _, _, _) => Draw { x, y, n },
+        (0xE, _, 9, 0xE) => SkipKey { x },
+        (0xE, _, 0xA, 1) => SkipNotKey { x },
+        (0xF, _, 0, 1) => Plane { mask: digit2 as u8 },
+        (0xF, _, 0, 7) => LoadDelay { x },
+        (0xF, _, 0, 0xA) => WaitKey { x },
+        (0xF, _, 1, 5) => SetDelay { x },
+        (0xF, _, 1, 8) => SetSound { x },
+        (0xF, _, 1, 0xE) => AddI { x },
+        (0xF, _, 2, 9) => Font { x },
+        (0xF, _, 3, 0) => BigFont { x },
+        (0xF, _, 3, 3) => Bcd { x },
+        (0xF, _, 5, 5) => Store { x },
+        (0xF, _, 6, 5) => Load { x },
+        (0xF, _, 7, 5) => StoreFlags { x },
+        (0xF, _, 8, 5) => LoadFlags { x },
+        (_, _, _, _) => Unknown(op),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    
+    #[test]
+    fn decodes_operands() {
+        assert_eq!(decode(0x00C7), Instruction::ScrollDown { n: 7 });
+        assert_eq!(decode(0x0123), Instruction::MachineCall { nnn: 0x123 });
+        assert_eq!(decode(0x00E1), Instruction::MachineCall { nnn: 0x0E1 });
+        assert_eq!(decode(0x1ABC), Instruction::Jump { nnn: 0xABC });
+        assert_eq!(decode(0x6A02), Instruction::LoadByte { x: 0xA, nn: 0x02 });
+        assert_eq!(decode(0x8AB6), Instruction::Shr { x: 0xA, y: 0xB });
+        assert_eq!(decode(0xB3FF), Instruction::JumpOffset { x: 3, nnn: 0x3FF });
+        assert_eq!(decode(0xD12F), Instruction::Draw { x: 1, y: 2, n: 0xF });
+        assert_eq!(decode(0xF201), Instruction::Plane { mask: 2 });
+        assert_eq!(decode(0xF565), Instruction::Load { x: 5 });
+    }
+
+    #[test]
+    fn unknown_words() {
+        for op in [0x5121, 0x800F, 0x9121, 0xE000, 0xF0FF] {
+            assert_eq!(decode(op), Instruction::Unknown(op));
+        }
+    }
+
+    // Whether `op` fits a pattern like "8XY4"
+    fn matches_pattern(op: u16, pattern: &str) -> bool {
+        pattern.chars().enumerate().all(|(idx, c)| {
+            let digit = (op >> (12 - 4 * idx)) & 0xF;
+            c.to_digit(16).is_none_or(|want| want as u16 == digit)
+        })
+    }
+
+    #[test]
+    fn implemented_opcodes_match_decode() {
+        // every pattern decodes with its operand nibbles filled in...
+        for pattern in implemented_opcodes() {
+            let word: String = pattern
+                .chars()
+                .map(|c| match c {
+                    'X' => '1',
+                    'Y' => '2',
+                    'N' => '3',
+                    c => c,
+                })
+                .collect();
+            let op = u16::from_str_radix(&word, 16).unwrap();
+            assert_ne!(decode(op), Instruction::Unknown(op), "{}", pattern);
+        }
+        // ...and every word that decodes fits one of them
+        for op in 0..=0xFFFF_u16 {
+            if decode(op) != Instruction::Unknown(op) {
+                assert!(implemented_opcodes().iter().any(|p| matches_pattern(op, p)), "{:04X}", op);
+            }
+        }
+    }
+
+    #[test]
+    fn core_opcodes_implemented() {
+        // the original COSMAC VIP instruction set
+        let core = [
+            "00E0", "00EE", "0NNN", "1NNN", "2NNN", "3XNN", "4XNN", "5XY0", "6XNN", "7XNN",
+            "8XY0", "8XY1", "8XY2", "8XY3", "8XY4", "8XY5", "8XY6", "8XY7", "8XYE", "9XY0",
+            "ANNN", "BNNN", "CXNN", "DXYN", "EX9E", "EXA1", "FX07", "FX0A", "FX15", "FX18",
+            "FX1E", "FX29", "FX33", "FX55", "FX65",
+        ];
+        let missing: Vec<_> =
+            core.iter().filter(|op| !implemented_opcodes().contains(op)).collect();
+        assert!(missing.is_empty(), "{:?}", missing);
+    }
+
+    #[test]
+    fn unknown_word_count() {
+        // the 21752 words execute's old nibble match fell through on, less the 4072 0NNN
+        // machine calls and the 256 9XY0 skips
+        let unknown = (0..=0xFFFF_u16).filter(|&op| decode(op) == Instruction::Unknown(op));
+        assert_eq!(unknown.count(), 17424);
+    }
+}
@@ -0,0 +1,154 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::{decode, Emu, Instruction};
+
+// Decodes a single opcode into a human-readable mnemonic, e.g. 0x6A02 -> "LD V10, 0x02".
+// Registers are written as V0 - V15 and words that aren't a known instruction come back as
+// "DB 0xXXXX" so raw data can still be listed
+pub fn disassemble(op: u16) -> String {
+    use Instruction::*;
+    match decode(op) {
+        Nop => "NOP".to_string(),
+        ScrollDown { n } => format!("SCD {}", n),
+        Cls => "CLS".to_string(),
+        Ret => "RET".to_string(),
+        ScrollRight => "SCR".to_string(),
+        ScrollLeft => "SCL".to_string(),
+        Exit => "EXIT".to_string(),
+        LoRes => "LOW".to_string(),
+        HiRes => "HIGH".to_string(),
+        MachineCall { nnn } => format!("SYS 0x{:03X}", nnn),
+        Jump { nnn } => format!("JMP 0x{:03X}", nnn),
+        Call { nnn } => format!("CALL 0x{:03X}", nnn),
+        SkipEqByte { x, nn } => format!("SE V{}, 0x{:02X}", x, nn),
+        SkipNeByte { x, nn } => format!("SNE V{}, 0x{:02X}", x, nn),
+        SkipEqReg { x, y } => format!("SE V{}, V{}", x, y),
+        SkipNeReg { x, y } => format!("SNE V{}, V{}", x, y),
+        LoadByte { x, nn } => format!("LD V{}, 0x{:02X}", x, nn),
+        AddByte { x, nn } => format!("ADD V{}, 0x{:02X}", x, nn),
+        LoadReg { x, y } => format!("LD V{}, V{}", x, y),
+        Or { x, y } => format!("OR V{}, V{}", x, y),
+        And { x, y } => format!("AND V{}, V{}", x, y),
+        Xor { x, y } => format!("XOR V{}, V{}", x, y),
+        AddReg { x, y } => format!("ADD V{}, V{}", x, y),
+        Sub { x, y } => format!("SUB V{}, V{}", x, y),
+        Shr { x, y } => format!("SHR V{}, V{}", x, y),
+        SubN { x, y } => format!("SUBN V{}, V{}", x, y),
+        Shl { x, y } => format!("SHL V{}, V{}", x, y),
+        LoadI { nnn } => format!("LD I, 0x{:03X}", nnn),
+        JumpOffset { nnn, .. } => format!("JMP V0, 0x{:03X}", nnn),
+        Random { x, nn } => format!("RND V{}, 0x{:02X}", x, nn),
+        Draw { x, y, n } => format!("DRW V{}, V{}, {}", x, y, n),
+        SkipKey { x } => format!("SKP V{}", x),
+        SkipNotKey { x } => format!("SKNP V{}", x),
+        Plane { mask } => format!("PLANE {}", mask),
+        LoadDelay { x } => format!("LD V{}, DT", x),
+        WaitKey { x } => format!("LD V{}, K", x),
+        SetDelay { x } => format!("LD DT, V{}", x),
+        SetSound { x } => format!("LD ST, V{}", x),
+        AddI { x } => format!("ADD I, V{}", x),
+        Font { x } => format!("LD F, V{}", x),
+        BigFont { x } => format!("LD HF, V{}", x),
+        Bcd { x } => format!("LD B, V{}", x),
+        Store { x } => format!("LD [I], V{}", x),
+        Load { x } => format!("LD V{}, [I]", x),
+        StoreFlags { x } => format!("LD R, V{}", x),
+        LoadFlags { x } => format!("LD V{}, R", x),
+        Unknown(op) => format!("DB 0x{:04X}", op),
+    }
+}
+
+impl Emu {
+    // Lists (address, opcode, mnemonic) for the big-endian words in `len` bytes of RAM from
+    // `start`. Any address is allowed, odd ones included, and the listing stops early at the
+    // last full word before the end of RAM
+    pub fn disassemble_range(&self, start: usize, len: usize) -> Vec<(u16, u16, String)> {
+        let end = start.saturating_add(len).min(self.ram.len());
+        (start..end)
+            .step_by(2)
+            .take_while(|&addr| addr + 1 < end)
+            .map(|addr| {
+                let op = (self.ram[addr] as u16) << 8 | self.ram[addr + 1] as u16;
+                (addr as u16, op, disassemble(op))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_sample() {
+        assert_eq!(disassemble(0x0000), "NOP");
+        assert_eq!(disassemble(0x00E0), "CLS");
+        assert_eq!(disassemble(0x00EE), "RET");
+        assert_eq!(disassemble(0x00C4), "SCD 4");
+        assert_eq!(disassemble(0x1200), "JMP 0x200");
+        assert_eq!(disassemble(0x2ABC), "CALL 0xABC");
+        assert_eq!(disassemble(0x3A02), "SE V10, 0x02");
+        assert_eq!(disassemble(0x5120), "SE V1, V2");
+        assert_eq!(disassemble(0x6A02), "LD V10, 0x02");
+        assert_eq!(disassemble(0x7FFF), "ADD V15, 0xFF");
+        assert_eq!(disassemble(0x8014), "ADD V0, V1");
+        assert_eq!(disassemble(0x801E), "SHL V0, V1");
+        assert_eq!(disassemble(0x9AB0), "SNE V10, V11");
+        assert_eq!(disassemble(0xA123), "LD I, 0x123");
+        assert_eq!(disassemble(0xB300), "JMP V0, 0x300");
+        assert_eq!(disassemble(0xC30F), "RND V3, 0x0F");
+        assert_eq!(disassemble(0xD015), "DRW V0, V1, 5");
+        assert_eq!(disassemble(0xE29E), "SKP V2");
+        assert_eq!(disassemble(0xE2A1), "SKNP V2");
+        assert_eq!(disassemble(0xF30A), "LD V3, K");
+        assert_eq!(disassemble(0xF129), "LD F, V1");
+        assert_eq!(disassemble(0xF355), "LD [I], V3");
+        assert_eq!(disassemble(0xF365), "LD V3, [I]");
+        assert_eq!(disassemble(0x0123), "SYS 0x123");
+    }
+
+    #[test]
+    fn disassemble_unknown_as_data() {
+        assert_eq!(disassemble(0x5121), "DB 0x5121");
+        assert_eq!(disassemble(0xE0FF), "DB 0xE0FF");
+        assert_eq!(disassemble(0xFFFF), "DB 0xFFFF");
+    }
+
+    #[test]
+    fn disassemble_loaded_program() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x60, 0x05, 0xA0, 0x50, 0xD0, 0x05, 0x12, 0x06]).unwrap();
+        assert_eq!(
+            emu.disassemble_range(0x200, 8),
+            vec![
+                (0x200, 0x6005, "LD V0, 0x05".to_string()),
+                (0x202, 0xA050, "LD I, 0x050".to_string()),
+                (0x204, 0xD005, "DRW V0, V0, 5".to_string()),
+                (0x206, 0x1206, "JMP 0x206".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn disassemble_odd_address() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x00, 0x60, 0x05, 0x00]).unwrap();
+        assert_eq!(
+            emu.disassemble_range(0x201, 3),
+            vec![(0x201, 0x6005, "LD V0, 0x05".to_string())]
+        );
+    }
+
+    #[test]
+    fn disassemble_range_past_end_of_ram() {
+        let emu = Emu::new();
+        let listing = emu.disassemble_range(0xFFC, 100);
+        assert_eq!(listing.len(), 2);
+        assert_eq!(listing[1].0, 0xFFE);
+        assert!(emu.disassemble_range(0xFFF, 2).is_empty());
+        assert!(emu.disassemble_range(0x2000, 2).is_empty());
+        assert!(emu.disassemble_range(usize::MAX, usize::MAX).is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+// Headless runner for test ROMs. Most test suites draw a pass/fail grid and then spin, so
+// running for a fixed number of cycles and comparing render_ascii turns them into regression
+// tests
+use crate::{Chip8Config, Chip8Error, Emu};
+
+// Loads `rom` into a fresh emulator and runs it until it has used up `cycles` cycles, counted
+// like cycle_count with each instruction taking its cycle_cost. The last instruction can take
+// it a little past. The timers tick once every ticks_per_frame cycles, the rate step runs them
+// at. Stops early once 00FD halts and hands back the emulator so the screen can be checked
+pub fn run_rom_for_cycles(rom: &[u8], cycles: u64) -> Result<Emu, Chip8Error> {
+    run_rom_for_cycles_with_config(rom, cycles, Chip8Config::default())
+}
+
+// run_rom_for_cycles under `config`, for test ROMs like the quirks test whose results depend
+// on the interpreter being emulated
+pub fn run_rom_for_cycles_with_config(
+    rom: &[u8],
+    cycles: u64,
+    config: Chip8Config,
+) -> Result<Emu, Chip8Error> {
+    let mut emu = Emu::with_config(config);
+    emu.load_rom(rom)?;
+    run_for_cycles(&mut emu, cycles)?;
+    Ok(emu)
+}
+
+// Runs an emulator that's already loaded until cycle_count reaches `cycles` or it halts
+fn run_for_cycles(emu: &mut Emu, cycles: u64) -> Result<(), Chip8Error> {
+    let ticks_per_frame = emu.config.ticks_per_frame.max(1) as u64;
+    let mut next_frame = ticks_per_frame;
+    while emu.cycle_count() < cycles {
+        match emu.tick() {
+            Ok(_) => (),
+            Err(Chip8Error::Halted) => break,
+            Err(err) => return Err(err),
+        }
+        while emu.cycle_count() >= next_frame {
+            emu.tick_timers();
+            next_frame += ticks_per_frame;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    // Timendus' CHIP-8 test suite, see roms/README.md. Writing a test's number to 0x1FF starts it
+    // straight away instead of going through the menu, and for the quirks test 0x1FE picks the
+    // platform - 1 for CHIP-8, 2 for SCHIP
+    const TEST_SUITE: &[u8] = include_bytes!("../roms/chip8-test-suite.ch8");
+    const FLAGS_TEST: u8 = 3;
+    const QUIRKS_TEST: u8 = 4;
+
+    fn run_test_suite(test: u8, platform: u8, config: Chip8Config) -> Emu {
+        let mut emu = Emu::with_config(config);
+        emu.load_rom(TEST_SUITE).unwrap();
+        emu.ram[0x1FF] = test;
+        emu.ram[0x1FE] = platform;
+        run_for_cycles(&mut emu, 50_000).unwrap();
+        emu
+    }
+
+    // The screen a test leaves behind, '#' for lit pixels, against its saved pass screen
+    fn assert_screen(emu: &Emu, expected: &str) {
+        let screen = emu.render_ascii_with('#', '.');
+        assert_eq!(screen, expected, "\n{}", screen);
+    }
+
+    #[test]
+    fn test_suite_flags_pass() {
+        let emu = run_test_suite(FLAGS_TEST, 0, Chip8Config::default());
+        // every flag check in the HAPPY, CARRY and OTHER rows gets a tick
+        assert_screen(&emu, include_str!("../roms/flags-pass.txt"));
+        assert!(emu.is_waiting_for_key());
+    }
+
+    #[test]
+    fn test_suite_quirks_pass() {
+        let emu = run_test_suite(QUIRKS_TEST, 1, Chip8Config::cosmac_vip());
+        assert_screen(&emu, include_str!("../roms/quirks-chip8-pass.txt"));
+        let emu = run_test_suite(QUIRKS_TEST, 2, Chip8Config::schip());
+        assert_screen(&emu, include_str!("../roms/quirks-schip-pass.txt"));
+    }
+
+    // roms/flags.asm is our own ROM, written alongside the assembler
+    #[test]
+    fn flags_rom_passes() {
+        let rom = assemble(include_str!("../roms/flags.asm")).unwrap();
+        let emu = run_rom_for_cycles(&rom, 10_000).unwrap();
+        assert!(emu.is_halted());
+
+        // eight As across the top, one per check
+        let glyph = ["████ ", "█  █ ", "████ ", "█  █ ", "█  █ "];
+        let screen = emu.render_ascii();
+        for (y, line) in screen.lines().enumerate() {
+            let expected = match glyph.get(y) {
+                Some(row) => format!("{:<64}", row.repeat(8)),
+                None => " ".repeat(64),
+            };
+            assert_eq!(line, expected, "row {}\n{}", y, screen);
+        }
+    }
+
+    #[test]
+    fn stops_after_cycles() {
+        // ADD V0, 1 / JMP 0x200
+        let emu = run_rom_for_cycles(&[0x70, 0x01, 0x12, 0x00], 9).unwrap();
+        assert_eq!(emu.v_reg(0), 5);
+        assert_eq!(emu.cycle_count(), 9);
+    }
+
+    #[test]
+    fn counts_cycles_not_instructions() {
+        // LD V0, 0 / DRW V0, V0, 8 / ADD V1, 1 / JMP 0x202 - each pass costs 5 + 1 + 1 cycles
+        let rom = [0x60, 0x00, 0xD0, 0x08, 0x71, 0x01, 0x12, 0x02];
+        let emu = run_rom_for_cycles(&rom, 1 + 7 * 4).unwrap();
+        assert_eq!(emu.v_reg(1), 4);
+        assert_eq!(emu.cycle_count(), 29);
+    }
+
+    #[test]
+    fn runs_under_config() {
+        // LD V0, 0 / LD V2, 2 / JMP V0, 0x208 / NOP / ADD V1, 1 / EXIT. BXNN jumps past the
+        // ADD with the jump quirk, since it adds V2 rather than V0
+        let rom = [0x60, 0x00, 0x62, 0x02, 0xB2, 0x08, 0x00, 0x00, 0x71, 0x01, 0x00, 0xFD];
+        let emu = run_rom_for_cycles(&rom, 100).unwrap();
+        assert_eq!(emu.v_reg(1), 1);
+        let emu = run_rom_for_cycles_with_config(&rom, 100, Chip8Config::schip()).unwrap();
+        assert!(emu.is_halted());
+        assert_eq!(emu.v_reg(1), 0);
+    }
+
+    #[test]
+    fn errors_pass_through() {
+        let err = run_rom_for_cycles(&[0x00, 0xEE], 1).err();
+        assert_eq!(err, Some(Chip8Error::StackUnderflow));
+    }
+}
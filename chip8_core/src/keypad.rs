@@ -0,0 +1,123 @@
+// Maps physical keys from a frontend onto the 16 key hex keypad, so every frontend doesn't
+// have to hardcode its own layout. Physical keys are plain u8 codes, ASCII for keyboards
+use crate::{Emu, NUM_KEYS};
+
+// The COSMAC VIP keypad laid out on the left of a QWERTY keyboard
+//   1 2 3 C    1 2 3 4
+//   4 5 6 D    Q W E R
+//   7 8 9 E    A S D F
+//   A 0 B F    Z X C V
+const COSMAC_LAYOUT: [(u8, u8); NUM_KEYS] = [
+    (b'1', 0x1), (b'2', 0x2), (b'3', 0x3), (b'4', 0xC),
+    (b'Q', 0x4), (b'W', 0x5), (b'E', 0x6), (b'R', 0xD),
+    (b'A', 0x7), (b'S', 0x8), (b'D', 0x9), (b'F', 0xE),
+    (b'Z', 0xA), (b'X', 0x0), (b'C', 0xB), (b'V', 0xF),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keypad {
+    // CHIP-8 key for every physical key code, None when unbound
+    map: [Option<u8>; 256],
+}
+
+impl Keypad {
+    // A keypad with nothing bound
+    pub fn empty() -> Self {
+        Self { map: [None; 256] }
+    }
+
+    // Binds `physical` to CHIP-8 key `key` (0x0 - 0xF), out of range keys are ignored
+    pub fn bind(&mut self, physical: u8, key: u8) {
+        if (key as usize) < NUM_KEYS {
+            self.map[physical as usize] = Some(key);
+        }
+    }
+
+    pub fn unbind(&mut self, physical: u8) {
+        self.map[physical as usize] = None;
+    }
+
+    // CHIP-8 key bound to `physical`, if any
+    pub fn key_for(&self, physical: u8) -> Option<u8> {
+        self.map[physical as usize]
+    }
+
+    // Presses the CHIP-8 key bound to `physical`, returns false when it isn't bound
+    pub fn press(&self, emu: &mut Emu, physical: u8) -> bool {
+        self.set(emu, physical, true)
+    }
+
+    // Releases the CHIP-8 key bound to `physical`, returns false when it isn't bound
+    pub fn release(&self, emu: &mut Emu, physical: u8) -> bool {
+        self.set(emu, physical, false)
+    }
+
+    fn set(&self, emu: &mut Emu, physical: u8, pressed: bool) -> bool {
+        match self.key_for(physical) {
+            Some(key) => {
+                emu.keypress(key as usize, pressed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// The COSMAC layout, bound for both upper and lower case letters
+impl Default for Keypad {
+    fn default() -> Self {
+        let mut keypad = Self::empty();
+        for (physical, key) in COSMAC_LAYOUT {
+            keypad.bind(physical, key);
+            keypad.bind(physical.to_ascii_lowercase(), key);
+        }
+        keypad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_cosmac_layout() {
+        let keypad = Keypad::default();
+        assert_eq!(keypad.key_for(b'1'), Some(0x1));
+        assert_eq!(keypad.key_for(b'4'), Some(0xC));
+        assert_eq!(keypad.key_for(b'W'), Some(0x5));
+        assert_eq!(keypad.key_for(b'w'), Some(0x5));
+        assert_eq!(keypad.key_for(b'X'), Some(0x0));
+        assert_eq!(keypad.key_for(b'v'), Some(0xF));
+        assert_eq!(keypad.key_for(b'P'), None);
+        let bound = (0..=255).filter(|&code| keypad.key_for(code).is_some()).count();
+        assert_eq!(bound, 4 + 12 * 2);
+    }
+
+    #[test]
+    fn press_and_release_drive_emu_keys() {
+        let keypad = Keypad::default();
+        let mut emu = Emu::new();
+        assert!(keypad.press(&mut emu, b's'));
+        assert!(emu.keys[0x8]);
+        assert!(keypad.release(&mut emu, b'S'));
+        assert!(!emu.keys[0x8]);
+        assert!(!keypad.press(&mut emu, b'P'));
+        assert!(emu.keys.iter().all(|&k| !k));
+    }
+
+    #[test]
+    fn custom_remap() {
+        let mut keypad = Keypad::default();
+        keypad.unbind(b'x');
+        keypad.unbind(b'X');
+        keypad.bind(b' ', 0x0);
+        // out of range keys don't bind
+        keypad.bind(b'P', 0x10);
+        assert_eq!(keypad.key_for(b'X'), None);
+        assert_eq!(keypad.key_for(b'P'), None);
+
+        let mut emu = Emu::new();
+        assert!(keypad.press(&mut emu, b' '));
+        assert!(emu.keys[0x0]);
+    }
+}
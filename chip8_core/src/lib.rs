@@ -1,12 +1,66 @@
+// Only core and alloc are used outside of the rand-backed default RNG, so the crate builds
+// for bare-metal targets with `default-features = false`
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+// The tests lean on std (RefCell, format!, temp files) even when the crate itself is no_std
+#[cfg(test)]
+#[macro_use]
+extern crate std;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+mod asm;
+mod audio;
+mod config;
+mod debug;
+mod decode;
+mod disasm;
+mod harness;
+mod keypad;
+#[cfg(feature = "std")]
+mod loader;
+mod render;
+mod rom_format;
+#[cfg(feature = "image")]
+mod screenshot;
+#[cfg(feature = "serde")]
+mod serialize;
+mod state;
+mod timing;
+
+pub use asm::{assemble, AsmError};
+pub use audio::DEFAULT_BEEP_HZ;
+pub use config::{detect_quirks, Chip8Config, UnknownOpcode};
+pub use debug::StopReason;
+pub use decode::{decode, implemented_opcodes, Instruction};
+pub use disasm::disassemble;
+pub use harness::{run_rom_for_cycles, run_rom_for_cycles_with_config};
+pub use keypad::Keypad;
+pub use render::{ASCII_OFF, ASCII_ON, DEFAULT_PALETTE};
+pub use rom_format::{detect_rom_format, RomFormat};
+pub use timing::cycle_cost;
+
 // 64x32 monochrome display (1 bit per pixel)
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+// SCHIP 128x64 high resolution mode
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+// XO-CHIP draws on two bit planes, giving each pixel a 2-bit color
+pub const NUM_PLANES: usize = 2;
 
-const RAM_SIZE: usize = 4096; // 4kb
+const RAM_SIZE: usize = 4096; // 4kb, the default for Chip8Config::ram_size
 const NUM_REGS: usize = 16; // # of V register
 const STACK_SIZE: usize = 16; // Stack Pointer (SP)
 const NUM_KEYS: usize = 16;
 const FONTSET_SIZE: usize = 80;
+const NUM_RPL_FLAGS: usize = 8;
 
 const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -27,12 +81,165 @@ const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80 // F
 ];
 
+// SCHIP 8x10 font, loaded into RAM right after FONTSET
+const BIG_FONTSET_SIZE: usize = 160;
+
+const BIG_FONTSET: [u8; BIG_FONTSET_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0 // F
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    // CALL with all STACK_SIZE return addresses already in use
+    StackOverflow,
+    // RET with no return address on the stack
+    StackUnderflow,
+    // PC doesn't leave room for a full opcode before the end of RAM
+    PcOutOfBounds,
+    // ROM doesn't fit in the RAM after the start address
+    RomTooLarge { size: usize, max: usize },
+    // tick after 00FD stopped the interpreter
+    Halted,
+    // load_state given a blob that isn't a complete save state in the current format
+    InvalidSaveState,
+    // with_fontset given a font that doesn't fit in the RAM after its offset
+    FontsetTooLarge { size: usize, max: usize },
+    // Word that isn't an instruction, with Chip8Config::on_unknown_opcode set to Error
+    UnknownOpcode(u16),
+    // 0NNN machine code call, with Chip8Config::on_machine_call set to Error
+    UnsupportedMachineCall(u16),
+    // load_rom_auto given an Octo cartridge, which holds source code rather than a program
+    OctoCartridge,
+}
+
+// Messages for frontends to show the user, Debug still has the variant names
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::StackOverflow => {
+                write!(f, "call stack overflow, all {} return addresses are in use", STACK_SIZE)
+            }
+            Chip8Error::StackUnderflow => write!(f, "return with an empty call stack"),
+            Chip8Error::PcOutOfBounds => write!(f, "program counter ran off the end of RAM"),
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes but only {} fit in RAM", size, max)
+            }
+            Chip8Error::Halted => write!(f, "the program has exited"),
+            Chip8Error::InvalidSaveState => write!(f, "not a valid save state"),
+            Chip8Error::FontsetTooLarge { size, max } => {
+                write!(f, "fontset is {} bytes but only {} fit in RAM after its offset", size, max)
+            }
+            Chip8Error::UnknownOpcode(op) => write!(f, "unknown opcode {:04X}", op),
+            Chip8Error::UnsupportedMachineCall(op) => {
+                write!(f, "machine code call {:04X} isn't supported", op)
+            }
+            Chip8Error::OctoCartridge => {
+                write!(f, "this is an Octo cartridge, export it from Octo as a binary to run it")
+            }
+        }
+    }
+}
+
+impl core::error::Error for Chip8Error {}
+
+// Source of random bytes for CXNN - boxed on Emu so tests can inject a known sequence
+pub trait Rng {
+    fn next_u8(&mut self) -> u8;
+
+    // Generator state for save states, None (the default) when it can't be captured
+    fn state(&self) -> Option<u64> {
+        None
+    }
+
+    // Puts back a value returned by state()
+    fn set_state(&mut self, _state: u64) {}
+}
+
+// Any closure returning a byte can be used as an Rng, e.g. `Emu::with_rng(|| 0xAB)`
+impl<F: FnMut() -> u8> Rng for F {
+    fn next_u8(&mut self) -> u8 {
+        self()
+    }
+}
+
+// Default Rng backed by the rand crate
+#[cfg(feature = "std")]
+struct RandRng;
+
+#[cfg(feature = "std")]
+impl Rng for RandRng {
+    fn next_u8(&mut self) -> u8 {
+        rand::random()
+    }
+}
+
+// Deterministic 64-bit xorshift used by with_seed, and as the default Rng without std
+struct XorShiftRng(u64);
+
+impl XorShiftRng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on 0, so that seed is swapped for a fixed non-zero one
+        Self(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })
+    }
+}
+
+impl Rng for XorShiftRng {
+    fn next_u8(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 56) as u8
+    }
+
+    fn state(&self) -> Option<u64> {
+        Some(self.0)
+    }
+
+    fn set_state(&mut self, state: u64) {
+        *self = Self::new(state);
+    }
+}
+
+#[cfg(feature = "std")]
+fn default_rng() -> Box<dyn Rng> {
+    Box::new(RandRng)
+}
+
+#[cfg(not(feature = "std"))]
+fn default_rng() -> Box<dyn Rng> {
+    Box::new(XorShiftRng::new(0))
+}
+
 pub struct Emu {
     // Program Counter (PC) - special register that stores index of current instruction
     pc: u16,
-    // 4,096 bytes of ram
-    ram: [u8; RAM_SIZE],
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    // config.ram_size bytes of ram, 4,096 by default
+    ram: Vec<u8>,
+    // Bit planes with one u128 word per row, pixel x of a row is bit x. Sized for hires, only
+    // the first display_height() rows and display_width() bits are in use. Plane 0 is the
+    // classic monochrome screen, plane 1 is the XO-CHIP second plane
+    planes: [[u128; HIRES_HEIGHT]; NUM_PLANES],
+    // Rows changed since the last take_dirty_rows, bit N is row N
+    dirty_rows: u64,
+    // XO-CHIP planes selected by FN01 for drawing, clearing and scrolling, bit 0 is plane 0
+    plane_mask: u8,
+    // SCHIP high resolution mode, toggled by 00FF/00FE
+    hires: bool,
     // 16 V registers (V0-VF)
     v_reg: [u8; NUM_REGS],
     // I register
@@ -40,52 +247,255 @@ pub struct Emu {
     sp: u16,
     stack: [u16; STACK_SIZE],
     keys: [bool; NUM_KEYS],
+    // Recorded (key, pressed) events waiting to be applied, by the cycle_count they happen at.
+    // Kept across reset so a replay can be queued before restarting the game
+    key_events: BTreeMap<u64, Vec<(u8, bool)>>,
     // Delay Timer
     dt: u8,
     // Sound Timer
     st: u8,
+    // Interpreter quirks and timing
+    config: Chip8Config,
+    // 5 byte per glyph font used by FX29 and the RAM address it's loaded at, reloaded by reset
+    fontset: Vec<u8>,
+    font_addr: u16,
+    // SCHIP RPL user flags, persistent storage for FX75/FX85 so they're kept across reset
+    rpl_flags: [u8; NUM_RPL_FLAGS],
+    // Set while FX0A is blocking on a keypress
+    waiting_for_key: bool,
+    // Set at every frame boundary by tick_timers and cleared by the next instruction to run, so
+    // under display_wait_quirk a DXYN only goes ahead straight after a boundary
+    frame_ready: bool,
+    // Set by 00FD, no further instructions run until reset
+    halted: bool,
+    // Set by pause, tick does nothing until resume. Left alone by reset
+    paused: bool,
+    // Whether the most recent DXYN turned any pixel off
+    last_draw_collided: bool,
+    // Total cycle_cost of the instructions executed since construction or the last reset
+    cycles: u64,
+    // Beep pitch for audio_samples and how far through the current cycle the wave is (0 - 1)
+    beep_hz: f32,
+    beep_phase: f32,
+    // Called by tick_timers with true when the beep starts and false when it stops, sound_on
+    // is what it was last told
+    sound_callback: Option<Box<dyn FnMut(bool)>>,
+    sound_on: bool,
+    // Time carried over between step() calls, in nanoseconds scaled by the CPU and timer
+    // rates so a whole cycle/timer tick is exactly NANOS_PER_SEC
+    cpu_accum: u128,
+    timer_accum: u128,
+    // save_state snapshots taken at the start of each run_frame, newest at the back
+    rewind_buffer: VecDeque<Vec<u8>>,
+    // Number of frames kept in rewind_buffer, 0 disables rewinding
+    rewind_depth: usize,
+    // Debugger breakpoints, kept across reset
+    breakpoints: BTreeSet<u16>,
+    // Called with (pc, opcode) for every instruction before it executes
+    trace_hook: Option<Box<dyn FnMut(u16, u16)>>,
+    // Called with (address, new value) when an opcode writes to a watched address
+    watchpoints: BTreeMap<u16, Box<dyn FnMut(u16, u8)>>,
+    // Executions of each opcode, only counted while profiling is on
+    profiling: bool,
+    opcode_histogram: BTreeMap<u16, u64>,
+    rng: Box<dyn Rng>,
+}
 
+const START_ADDR: u16 = 0x200; // 512, the default for Chip8Config::start_addr
+// Smallest RAM that fits both fonts and the most 16-bit addresses can reach
+const MIN_RAM_SIZE: usize = FONTSET_SIZE + BIG_FONTSET_SIZE;
+const MAX_RAM_SIZE: usize = 0x10000;
+const TIMER_HZ: u128 = 60;
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+impl Default for Emu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-const START_ADDR: u16 = 0x200; // 512
+// A full copy of the machine for forking or snapshots. The trace hook, watchpoint and sound
+// callbacks can't be copied so the clone starts without them. An RNG that exposes its state
+// continues the same sequence in the clone through an XorShiftRng, any other gets a fresh
+// default RNG
+impl Clone for Emu {
+    fn clone(&self) -> Self {
+        let rng: Box<dyn Rng> = match self.rng.state() {
+            Some(state) => Box::new(XorShiftRng::new(state)),
+            None => default_rng(),
+        };
+        Self {
+            pc: self.pc,
+            ram: self.ram.clone(),
+            planes: self.planes,
+            dirty_rows: self.dirty_rows,
+            plane_mask: self.plane_mask,
+            hires: self.hires,
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            sp: self.sp,
+            stack: self.stack,
+            keys: self.keys,
+            key_events: self.key_events.clone(),
+            dt: self.dt,
+            st: self.st,
+            config: self.config,
+            fontset: self.fontset.clone(),
+            font_addr: self.font_addr,
+            rpl_flags: self.rpl_flags,
+            waiting_for_key: self.waiting_for_key,
+            frame_ready: self.frame_ready,
+            halted: self.halted,
+            paused: self.paused,
+            last_draw_collided: self.last_draw_collided,
+            cycles: self.cycles,
+            beep_hz: self.beep_hz,
+            beep_phase: self.beep_phase,
+            sound_callback: None,
+            sound_on: self.sound_on,
+            cpu_accum: self.cpu_accum,
+            timer_accum: self.timer_accum,
+            rewind_buffer: self.rewind_buffer.clone(),
+            rewind_depth: self.rewind_depth,
+            breakpoints: self.breakpoints.clone(),
+            trace_hook: None,
+            watchpoints: BTreeMap::new(),
+            profiling: self.profiling,
+            opcode_histogram: self.opcode_histogram.clone(),
+            rng,
+        }
+    }
+}
 
 impl Emu {
     pub fn new() -> Self {
+        Self::with_config(Chip8Config::default())
+    }
+
+    // Panics if config.ram_size can't hold both fonts or is more than 16-bit addresses can reach
+    pub fn with_config(config: Chip8Config) -> Self {
+        Self::build(config, default_rng())
+    }
+
+    pub fn with_rng<R: Rng + 'static>(rng: R) -> Self {
+        Self::build(Chip8Config::default(), Box::new(rng))
+    }
+
+    // CXNN draws from a xorshift generator started at `seed`, so runs with the same seed and
+    // input are reproducible
+    pub fn with_seed(seed: u64) -> Self {
+        Self::build(Chip8Config::default(), Box::new(XorShiftRng::new(seed)))
+    }
+
+    fn build(config: Chip8Config, rng: Box<dyn Rng>) -> Self {
+        assert!(
+            (MIN_RAM_SIZE..=MAX_RAM_SIZE).contains(&config.ram_size),
+            "ram_size must be between {} and {} bytes",
+            MIN_RAM_SIZE,
+            MAX_RAM_SIZE
+        );
         let mut new_emu = Self {
-            pc: START_ADDR,
-            ram: [0; RAM_SIZE],
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            pc: config.start_addr,
+            ram: vec![0; config.ram_size],
+            planes: [[0; HIRES_HEIGHT]; NUM_PLANES],
+            dirty_rows: 0,
+            plane_mask: 1,
+            hires: false,
             v_reg: [0; NUM_REGS],
             i_reg: 0,
             sp: 0,
             stack: [0; STACK_SIZE],
             keys: [false; NUM_KEYS],
+            key_events: BTreeMap::new(),
             dt: 0,
             st: 0,
+            config,
+            fontset: FONTSET.to_vec(),
+            font_addr: 0,
+            rpl_flags: [0; NUM_RPL_FLAGS],
+            waiting_for_key: false,
+            frame_ready: true,
+            halted: false,
+            paused: false,
+            last_draw_collided: false,
+            cycles: 0,
+            beep_hz: DEFAULT_BEEP_HZ,
+            beep_phase: 0.0,
+            sound_callback: None,
+            sound_on: false,
+            cpu_accum: 0,
+            timer_accum: 0,
+            rewind_buffer: VecDeque::new(),
+            rewind_depth: 0,
+            breakpoints: BTreeSet::new(),
+            trace_hook: None,
+            watchpoints: BTreeMap::new(),
+            profiling: false,
+            opcode_histogram: BTreeMap::new(),
+            rng,
         };
+        new_emu.load_fonts();
+        new_emu
+    }
 
-        // copy_from_slice: Copies all elemenmts from src into self
-        // [..FONTSET_SIZE] means that we're essentially doing [0..80] then copying all the values
-        // of FONTSET into the 0..80 indices
-        new_emu.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+    // Swaps in a caller-supplied font with 5 byte glyphs at `offset` in RAM, FX29 points into
+    // it instead of the built-in FONTSET. The SCHIP big font stays where it is unless the new
+    // font is placed over it
+    pub fn with_fontset(data: &[u8], offset: usize) -> Result<Self, Chip8Error> {
+        let mut emu = Self::new();
+        let max = emu.ram.len().saturating_sub(offset);
+        if data.len() > max || offset >= emu.ram.len() {
+            return Err(Chip8Error::FontsetTooLarge { size: data.len(), max });
+        }
+        emu.fontset = data.to_vec();
+        emu.font_addr = offset as u16;
+        emu.ram[..FONTSET_SIZE].fill(0);
+        emu.load_fonts();
+        Ok(emu)
+    }
 
-        new_emu
+    fn load_fonts(&mut self) {
+        // copy_from_slice: Copies all elemenmts from src into self
+        // [FONTSET_SIZE..] means that we're essentially doing [80..240] then copying all the values
+        // of BIG_FONTSET into the 80..240 indices
+        self.ram[FONTSET_SIZE..FONTSET_SIZE + BIG_FONTSET_SIZE].copy_from_slice(&BIG_FONTSET);
+        // the small font goes in last so a custom one wins wherever it's placed
+        let start = self.font_addr as usize;
+        self.ram[start..start + self.fontset.len()].copy_from_slice(&self.fontset);
     }
 
-    fn push(&mut self, val: u16) {
+    fn push(&mut self, val: u16) -> Result<(), Chip8Error> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(Chip8Error::StackOverflow);
+        }
         self.stack[self.sp as usize] = val;
         self.sp += 1;
+        Ok(())
     }
 
-    fn pop(&mut self) -> u16 {
+    fn pop(&mut self) -> Result<u16, Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow);
+        }
         self.sp -= 1;
-        self.stack[self.sp as usize]
+        Ok(self.stack[self.sp as usize])
     }
 
+    // Cold boot - RAM is wiped back to just the fonts, so the ROM has to be loaded again
     pub fn reset(&mut self) {
-        self.pc = START_ADDR;
-        self.ram = [0; RAM_SIZE];
-        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.soft_reset();
+        self.ram.fill(0);
+        self.load_fonts();
+    }
+
+    // Like pressing reset on the machine - registers, stack, screen, timers and keys are cleared
+    // and PC goes back to the start address, but RAM and the program in it are left alone
+    pub fn soft_reset(&mut self) {
+        self.pc = self.config.start_addr;
+        self.planes = [[0; HIRES_HEIGHT]; NUM_PLANES];
+        self.dirty_rows = u64::MAX;
+        self.plane_mask = 1;
+        self.hires = false;
         self.v_reg = [0; NUM_REGS];
         self.i_reg = 0;
         self.sp = 0;
@@ -93,152 +503,2206 @@ impl Emu {
         self.keys = [false; NUM_KEYS];
         self.dt = 0;
         self.st = 0;
-        self.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        self.waiting_for_key = false;
+        self.frame_ready = true;
+        self.halted = false;
+        self.last_draw_collided = false;
+        self.cycles = 0;
+        self.cpu_accum = 0;
+        self.timer_accum = 0;
     }
 
-    pub fn tick(&mut self) {
-        // Fetch
-        let op = self.fetch();
-        self.execute(op);
+    // Copies a program into RAM at config.start_addr
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let start = self.config.start_addr as usize;
+        let max = self.ram.len().saturating_sub(start);
+        if data.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: data.len(), max });
+        }
+        self.ram[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Unpacks the `display_width() * display_height()` display into one `bool` per pixel in
+    /// row-major order. In the default lo-res mode that is `SCREEN_WIDTH * SCREEN_HEIGHT`.
+    ///
+    /// ```
+    /// use chip8_core::{Emu, SCREEN_WIDTH};
+    ///
+    /// let emu = Emu::new();
+    /// for (idx, on) in emu.get_display().enumerate() {
+    ///     let (x, y) = (idx % SCREEN_WIDTH, idx / SCREEN_WIDTH);
+    ///     assert!(!on, "pixel ({}, {}) is set on a fresh display", x, y);
+    /// }
+    /// ```
+    pub fn get_display(&self) -> impl Iterator<Item = bool> + '_ {
+        let (width, height) = (self.display_width(), self.display_height());
+        let rows = &self.planes[0][..height];
+        rows.iter().flat_map(move |row| (0..width).map(move |x| row >> x & 1 != 0))
+    }
+
+    // Per-pixel XO-CHIP color index (0 - 3) for the active display, bit N is set when the
+    // pixel is on in plane N
+    pub fn get_color_display(&self) -> Vec<u8> {
+        let (width, height) = (self.display_width(), self.display_height());
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (y, x)))
+            .map(|(y, x)| {
+                (0..NUM_PLANES).fold(0, |color, plane| {
+                    color | ((self.planes[plane][y] >> x & 1) as u8) << plane
+                })
+            })
+            .collect()
+    }
+
+    // Whether pixel (x, y) of plane 0 is on, false outside the active display
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        if x >= self.display_width() || y >= self.display_height() {
+            return false;
+        }
+        self.planes[0][y] >> x & 1 != 0
+    }
+
+    // Flips pixel (x, y) of plane 0 and returns whether it was on before, pixels outside the
+    // active display are left alone
+    pub fn toggle_pixel(&mut self, x: usize, y: usize) -> bool {
+        let was_on = self.get_pixel(x, y);
+        if x < self.display_width() && y < self.display_height() {
+            self.planes[0][y] ^= 1 << x;
+            self.dirty_rows |= 1 << y;
+        }
+        was_on
+    }
+
+    // Turns pixel (x, y) of plane 0 on or off, pixels outside the active display are left alone
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        if x >= self.display_width() || y >= self.display_height() {
+            return;
+        }
+        if on {
+            self.planes[0][y] |= 1 << x;
+        } else {
+            self.planes[0][y] &= !(1 << x);
+        }
+        self.dirty_rows |= 1 << y;
+    }
+
+    // Rows of the active display that changed since the last call, top to bottom. Frontends
+    // can redraw just these instead of the whole screen
+    pub fn take_dirty_rows(&mut self) -> Vec<usize> {
+        let dirty = self.dirty_rows;
+        self.dirty_rows = 0;
+        (0..self.display_height()).filter(|&y| dirty & (1 << y) != 0).collect()
+    }
+
+    // Bits of a row word that are on screen in the active resolution
+    fn row_mask(&self) -> u128 {
+        u128::MAX >> (HIRES_WIDTH - self.display_width())
     }
 
-    fn execute(&mut self, op: u16) {
-        // (from left to right)
-        // we mask the nibble at the position we want then shift all bits over all bits by a number
-        // that will place that nibble in the first four positions
-        // the last digit doesn't need a shift since it's already in the desired position
-        let digit1 = (op & 0xF000) >> 12;
-        let digit2 = (op & 0x0F00) >> 8;
-        let digit3 = (op & 0x00F0) >> 4;
-        let digit4 = op & 0x000F;
+    // Blanks the screen the same way 00E0 does, so only the planes selected by FN01 are
+    // cleared (just plane 0 unless an XO-CHIP game changed it). Every row is marked dirty
+    pub fn clear_display(&mut self) {
+        for plane in 0..NUM_PLANES {
+            if self.plane_selected(plane) {
+                self.planes[plane] = [0; HIRES_HEIGHT];
+            }
+        }
+        self.dirty_rows = u64::MAX;
+    }
 
-        match (digit1, digit2, digit3, digit4) {
+    // Width of the active resolution, HIRES_WIDTH in hires mode and SCREEN_WIDTH otherwise
+    pub fn display_width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { SCREEN_WIDTH }
+    }
+
+    // Height of the active resolution, HIRES_HEIGHT in hires mode and SCREEN_HEIGHT otherwise
+    pub fn display_height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { SCREEN_HEIGHT }
+    }
+
+    // (width, height) of the active resolution, so renderers don't have to assume 64x32
+    pub fn resolution(&self) -> (usize, usize) {
+        (self.display_width(), self.display_height())
+    }
+
+    // Sets the state of keypad key `idx` (0x0 - 0xF), out of range keys are ignored
+    pub fn keypress(&mut self, idx: usize, pressed: bool) {
+        if idx < NUM_KEYS {
+            self.keys[idx] = pressed;
+        }
+    }
+
+    // Whether keypad key `idx` is held, for debuggers and on-screen keypad overlays. Out of
+    // range keys are never pressed
+    pub fn is_key_pressed(&self, idx: usize) -> bool {
+        self.keys.get(idx).copied().unwrap_or(false)
+    }
+
+    // Releases every key at once, for when the frontend loses focus and won't see the key ups
+    pub fn clear_keys(&mut self) {
+        self.keys = [false; NUM_KEYS];
+    }
+
+    // Queues a keypress or release for tick to apply once cycle_count reaches `cycle`, so a
+    // recorded input log replays the same way every time when paired with with_seed
+    pub fn queue_key_event(&mut self, cycle: u64, key: u8, pressed: bool) {
+        self.key_events.entry(cycle).or_default().push((key, pressed));
+    }
+
+    // Drops any queued key events that haven't been applied yet
+    pub fn clear_key_events(&mut self) {
+        self.key_events.clear();
+    }
+
+    // Sets config.ticks_per_frame, how many instructions run_frame runs (and how fast step
+    // runs the CPU) for every timer tick. 10 suits most CHIP-8 games, SCHIP ones often want 30+
+    pub fn set_speed(&mut self, ticks_per_frame: usize) {
+        self.config.ticks_per_frame = ticks_per_frame;
+    }
+
+    // Runs one 60Hz frame - config.ticks_per_frame instructions followed by a single timer tick.
+    // Every instruction counts as one here whatever its cycle_cost, so a frame of draws runs
+    // more instructions than step would fit into 1/60s. Frontends that want cycle_cost timing
+    // should call step instead
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        self.push_rewind_state();
+        for _ in 0..self.config.ticks_per_frame {
+            self.tick()?;
+        }
+        self.tick_timers();
+        Ok(())
+    }
+
+    // Advances the emulator by `elapsed` of real time. The CPU runs at ticks_per_frame * 60
+    // cycles per second, with each instruction taking its cycle_cost, and the timers at 60Hz
+    // no matter how often this is called. Any leftover time is carried into the next call.
+    // Unlike run_frame this only matches ticks_per_frame instructions per frame when every
+    // instruction costs 1
+    pub fn step(&mut self, elapsed: Duration) -> Result<(), Chip8Error> {
+        let nanos = elapsed.as_nanos();
+        let cycles_per_sec = self.config.ticks_per_frame as u128 * TIMER_HZ;
+
+        // an instruction only runs once there's enough time banked for all of its cycles
+        self.cpu_accum += nanos * cycles_per_sec;
+        loop {
+            let cost = cycle_cost(self.peek_opcode()) as u128 * NANOS_PER_SEC;
+            if self.cpu_accum < cost {
+                break;
+            }
+            self.cpu_accum -= cost;
+            self.tick()?;
+        }
+
+        self.timer_accum += nanos * TIMER_HZ;
+        while self.timer_accum >= NANOS_PER_SEC {
+            self.timer_accum -= NANOS_PER_SEC;
+            self.tick_timers();
+        }
+        Ok(())
+    }
+
+    // Runs a single instruction and returns the opcode that was executed
+    pub fn tick(&mut self) -> Result<u16, Chip8Error> {
+        if self.halted {
+            return Err(Chip8Error::Halted);
+        }
+        // paused - nothing runs, hand back the opcode that's waiting at PC
+        if self.paused {
+            return Ok(self.peek_opcode());
+        }
+        // replayed input that's due, instructions can cost several cycles so anything
+        // stepped over is applied too
+        while let Some(events) = self.key_events.first_entry() {
+            if *events.key() > self.cycles {
+                break;
+            }
+            for (key, pressed) in events.remove() {
+                self.keypress(key as usize, pressed);
+            }
+        }
+        // Fetch
+        let pc = self.pc;
+        let op = self.fetch()?;
+        if let Some(hook) = self.trace_hook.as_mut() {
+            hook(pc, op);
+        }
+        self.execute(op)?;
+        // the frame boundary is used up once anything has run after it, so a DXYN always waits
+        // for the next one like the VIP's does
+        self.frame_ready = false;
+        self.cycles += cycle_cost(op) as u64;
+        if self.profiling {
+            *self.opcode_histogram.entry(op).or_insert(0) += 1;
+        }
+        Ok(op)
+    }
+
+    fn execute(&mut self, op: u16) -> Result<(), Chip8Error> {
+        use Instruction::*;
+        match decode(op) {
             // 0000 - NOP - Nop
-            (0, 0, 0, 0) => return,
+            Nop => (),
+            // 00CN - SCROLL DOWN N (SCHIP)
+            ScrollDown { n } => {
+                self.scroll_down(n as usize);
+            },
             // 00E0 - CLS - Clear screen
-            (0, 0, 0xE, 0) => {
-                self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+            Cls => {
+                self.clear_display();
             },
             // 00EE - RET - Return from Subroutine
-            (_, _, _, _) => unimplemented!("Unimplemented opcode: {}", op),
-            (0,0,0xE,0xE) => {
-                let ret_addr = self.pop();
+            Ret => {
+                let ret_addr = self.pop()?;
                 self.pc = ret_addr;
             },
+            // 00FB - SCROLL RIGHT 4 (SCHIP)
+            ScrollRight => {
+                self.scroll_right(4);
+            },
+            // 00FC - SCROLL LEFT 4 (SCHIP)
+            ScrollLeft => {
+                self.scroll_left(4);
+            },
+            // 00FD - EXIT - Stop the interpreter (SCHIP)
+            Exit => {
+                self.halted = true;
+            },
+            // 00FE - LORES - Switch to 64x32 (SCHIP)
+            LoRes => {
+                self.hires = false;
+                self.planes = [[0; HIRES_HEIGHT]; NUM_PLANES];
+                self.dirty_rows = u64::MAX;
+            },
+            // 00FF - HIRES - Switch to 128x64 (SCHIP)
+            HiRes => {
+                self.hires = true;
+                self.planes = [[0; HIRES_HEIGHT]; NUM_PLANES];
+                self.dirty_rows = u64::MAX;
+            },
+            // 0NNN - SYS NNN - Call a COSMAC VIP machine code routine, there's no 1802 to run it
+            MachineCall { .. } => match self.config.on_machine_call {
+                UnknownOpcode::Panic => panic!("machine code call {:04X}", op),
+                UnknownOpcode::Nop => (),
+                UnknownOpcode::Error => return Err(Chip8Error::UnsupportedMachineCall(op)),
+            },
             // 1NNN - JMP NNN - Jump
-            (1, _,_,_) => {
-                // 0xFFF gets us the lower 12 bits
-                // e.g.
-                /*    
-                      1010101111001101 (0xABCD)
-                   &  0000111111111111 (0xFFF)
-                      ----------------------
-                      0000101111001101 (0x0BCD)
-                */
-                let nnn = op & 0xFFF;
+            Jump { nnn } => {
                 self.pc = nnn;
             },
             // 2NNN - CALL NNN - Call Subroutine
-            (2,_,_,_) => {
-                let nnn = op & 0xFFF;
-                self.push(self.pc);
+            Call { nnn } => {
+                self.push(self.pc)?;
                 self.pc = nnn;
             },
             // 3XNN - SKIP VX == NN - Skip next if VX == NN
-            (3, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
+            SkipEqByte { x, nn } => {
                 if self.v_reg[x] == nn {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             },
             // 4XNN - SKIP VX != NN - Skip next if VX != NN
-            (4, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
+            SkipNeByte { x, nn } => {
                 if self.v_reg[x] != nn {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             },
             // 5XY0 - SKIP VX == VY - Skip next if VX == VY
-            (5, _, _, 0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            SkipEqReg { x, y } => {
                 if self.v_reg[x] == self.v_reg[y] {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             },
             // 6XNN - VX = NN
-            (6, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
+            LoadByte { x, nn } => {
                 self.v_reg[x] = nn;
             },
             // 7XNN - VX += NN
-            (7, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
-                self.v_reg[x] = self.v_reg[x].wrapping_add(nn); // wrapping_add for possible overflow
+            AddByte { x, nn } => {
+                // wrapping_add for possible overflow
+                self.v_reg[x] = self.v_reg[x].wrapping_add(nn);
             },
             // 8XY0 - VX = VY
-            (8,_,_,0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            LoadReg { x, y } => {
                 self.v_reg[x] = self.v_reg[y];
             },
             // 8XY1, 8XY2, 8XY3 - Bitwise operations
-            // 8XY1 - VX != VY
-            (8,_,_,1) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                self.v_reg[x] != self.v_reg[y];
+            // 8XY1 - VX |= VY
+            Or { x, y } => {
+                self.v_reg[x] |= self.v_reg[y];
+                if self.config.vf_reset_quirk {
+                    self.v_reg[0xF] = 0;
+                }
             },
             // 8XY2 - VX &= VY
-            (8,_,_,2) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            And { x, y } => {
                 self.v_reg[x] &= self.v_reg[y];
+                if self.config.vf_reset_quirk {
+                    self.v_reg[0xF] = 0;
+                }
             },
             // 8XY3 - VX ^= VY
-            (8,_,_,3) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Xor { x, y } => {
                 self.v_reg[x] ^= self.v_reg[y];
+                if self.config.vf_reset_quirk {
+                    self.v_reg[0xF] = 0;
+                }
             },
+            // 8XY4 - 8XYE work out the result and the flag from the old registers first, then
+            // set_result_and_flag stores them
             // 8XY4 - VX += VY
-            (8,_,_,4) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            AddReg { x, y } => {
                 let (new_vx, carry) = self.v_reg[x].overflowing_add(self.v_reg[y]);
-                let new_vf = if carry { 1 } else { 0 };
-                self.v_reg[x] = new_vx;
-                self.v_reg[0xF] = new_vf;
+                self.set_result_and_flag(x, new_vx, carry);
+            },
+            // 8XY5 - VX -= VY
+            Sub { x, y } => {
+                // VF is the inverse of the borrow, i.e. 1 when VX >= VY
+                let (new_vx, borrow) = self.v_reg[x].overflowing_sub(self.v_reg[y]);
+                self.set_result_and_flag(x, new_vx, !borrow);
+            },
+            // 8XY6 - VX >>= 1
+            Shr { x, y } => {
+                let src = if self.config.shift_quirk { self.v_reg[x] } else { self.v_reg[y] };
+                // the bit that gets shifted out (LSB) ends up in VF
+                self.set_result_and_flag(x, src >> 1, src & 1 != 0);
+            },
+            // 8XY7 - VX = VY - VX
+            SubN { x, y } => {
+                let (new_vx, borrow) = self.v_reg[y].overflowing_sub(self.v_reg[x]);
+                self.set_result_and_flag(x, new_vx, !borrow);
+            },
+            // 8XYE - VX <<= 1
+            Shl { x, y } => {
+                let src = if self.config.shift_quirk { self.v_reg[x] } else { self.v_reg[y] };
+                // the bit that gets shifted out (MSB) ends up in VF
+                self.set_result_and_flag(x, src << 1, src & 0x80 != 0);
+            },
+            // 9XY0 - SKIP VX != VY - Skip next if VX != VY
+            SkipNeReg { x, y } => {
+                if self.v_reg[x] != self.v_reg[y] {
+                    self.pc = self.pc.wrapping_add(2);
+                }
+            },
+            // ANNN - I = NNN
+            LoadI { nnn } => {
+                self.i_reg = nnn;
+            },
+            // BNNN - JMP V0 + NNN
+            JumpOffset { x, nnn } => {
+                let offset = if self.config.jump_quirk {
+                    self.v_reg[x]
+                } else {
+                    self.v_reg[0]
+                };
+                self.pc = nnn + offset as u16;
+            },
+            // CXNN - VX = rand() & NN
+            Random { x, nn } => {
+                let rng = self.rng.next_u8();
+                self.v_reg[x] = rng & nn;
+            },
+            // DXYN - DRAW - Draw an N-byte sprite from I at (VX, VY)
+            // DXY0 - DRAW - Draw a 16x16 sprite (two bytes per row) from I at (VX, VY) in hires,
+            // or 8x16 in lores (SCHIP)
+            Draw { x, y, n } => {
+                // COSMAC VIP - wait for vblank by re-running this opcode until the next frame
+                if self.config.display_wait_quirk {
+                    if !self.frame_ready {
+                        self.pc = self.pc.wrapping_sub(2);
+                        return Ok(());
+                    }
+                    self.frame_ready = false;
+                }
+                let (width, height) = (self.display_width(), self.display_height());
+                // the starting coordinates wrap onto the screen
+                let x_coord = self.v_reg[x] as usize % width;
+                let y_coord = self.v_reg[y] as usize % height;
+                // the last digit is the sprite height in rows (one byte per row). 0 means 16 rows,
+                // 16 pixels wide in hires but still 8 wide in lores like SCHIP 1.1
+                let (sprite_width, num_rows) = match n {
+                    0 if self.hires => (16, 16),
+                    0 => (8, 16),
+                    n => (8, n as usize),
+                };
+                let bytes_per_row = sprite_width / 8;
+                let row_mask = self.row_mask();
+                let mut collided = [false; 16];
+                // XO-CHIP - every selected plane gets its own copy of the sprite data, one
+                // after the other starting at I
+                let mut sprite_addr = self.i_reg as usize;
+                for plane in 0..NUM_PLANES {
+                    if !self.plane_selected(plane) {
+                        continue;
+                    }
+                    for (y_line, row_collided) in collided.iter_mut().enumerate().take(num_rows) {
+                        let y = y_coord + y_line;
+                        if self.config.clip_sprites && y >= height {
+                            continue;
+                        }
+                        let y = y % height;
+                        // sprite data running past the end of RAM wraps back around to address 0
+                        // like the VIP's 4KB address space does, rather than panicking
+                        let ram_len = self.ram.len();
+                        let addr = (sprite_addr + y_line * bytes_per_row) % ram_len;
+                        // line the row up MSB first in a u16 so both sprite widths are handled
+                        // the same, then reverse it so the leftmost pixel is bit 0 like the screen
+                        let pixels = if bytes_per_row == 2 {
+                            (self.ram[addr] as u16) << 8 | self.ram[(addr + 1) % ram_len] as u16
+                        } else {
+                            (self.ram[addr] as u16) << 8
+                        };
+                        let pixels = pixels.reverse_bits() as u128;
+                        // shift the sprite over to x, anything pushed past the right edge is
+                        // either dropped or wrapped back around to the left edge
+                        let mut sprite = (pixels << x_coord) & row_mask;
+                        if !self.config.clip_sprites {
+                            sprite |= pixels.checked_shr((width - x_coord) as u32).unwrap_or(0);
+                        }
+                        // a set pixel being flipped off is a collision
+                        let row = &mut self.planes[plane][y];
+                        *row_collided |= *row & sprite != 0;
+                        *row ^= sprite;
+                        self.dirty_rows |= 1 << y;
+                    }
+                    sprite_addr += bytes_per_row * num_rows;
+                }
+                let collided_rows = collided.iter().filter(|&&c| c).count() as u8;
+                self.last_draw_collided = collided_rows > 0;
+                // SCHIP reports the number of rows that collided in hires, plus any clipped off
+                // the bottom of the screen, otherwise VF is 0 or 1
+                let clipped_rows = if self.config.clip_sprites {
+                    num_rows.saturating_sub(height - y_coord) as u8
+                } else {
+                    0
+                };
+                self.v_reg[0xF] = if self.hires {
+                    collided_rows + clipped_rows
+                } else if collided_rows > 0 {
+                    1
+                } else {
+                    0
+                };
+            },
+            // EX9E - SKIP KEY PRESS - Skip next if key VX is pressed
+            SkipKey { x } => {
+                let vx = self.v_reg[x] as usize;
+                // a VX past the keypad counts as not pressed rather than indexing out of bounds
+                let key = self.keys.get(vx).copied().unwrap_or(false);
+                if key {
+                    self.pc = self.pc.wrapping_add(2);
+                }
+            },
+            // EXA1 - SKIP KEY RELEASE - Skip next if key VX is not pressed
+            SkipNotKey { x } => {
+                let vx = self.v_reg[x] as usize;
+                let key = self.keys.get(vx).copied().unwrap_or(false);
+                if !key {
+                    self.pc = self.pc.wrapping_add(2);
+                }
+            },
+            // FX0A - WAIT KEY - Wait for a keypress and store its index in VX
+            WaitKey { x } => {
+                match self.keys.iter().position(|&key| key) {
+                    Some(idx) => {
+                        self.v_reg[x] = idx as u8;
+                        self.waiting_for_key = false;
+                    },
+                    None => {
+                        // re-run this opcode next tick until a key is pressed
+                        self.pc = self.pc.wrapping_sub(2);
+                        self.waiting_for_key = true;
+                    },
+                }
+            },
+            // FN01 - PLANE N - Select the drawing planes (XO-CHIP)
+            Plane { mask } => {
+                self.plane_mask = mask & 0b11;
+            },
+            // FX07 - VX = DT
+            LoadDelay { x } => {
+                self.v_reg[x] = self.dt;
+            },
+            // FX15 - DT = VX
+            SetDelay { x } => {
+                self.dt = self.v_reg[x];
+            },
+            // FX18 - ST = VX
+            SetSound { x } => {
+                self.st = self.v_reg[x];
+            },
+            // FX1E - I += VX
+            AddI { x } => {
+                let vx = self.v_reg[x] as u16;
+                self.i_reg = self.i_reg.wrapping_add(vx);
+                if self.config.i_overflow_quirk {
+                    self.v_reg[0xF] = if self.i_reg > 0x0FFF { 1 } else { 0 };
+                }
+            },
+            // FX29 - I = FONT - Point I at the font sprite for the digit in VX
+            Font { x } => {
+                // every glyph is 5 bytes long, starting from wherever the fontset was loaded
+                let c = (self.v_reg[x] & 0xF) as u16;
+                self.i_reg = self.font_addr + c * 5;
+            },
+            // FX30 - I = BIG FONT - Point I at the 10-byte font sprite for the digit in VX (SCHIP)
+            BigFont { x } => {
+                let c = (self.v_reg[x] & 0xF) as u16;
+                self.i_reg = FONTSET_SIZE as u16 + c * 10;
+            },
+            // FX33 - BCD - Store the decimal digits of VX at I, I+1 and I+2, wrapping past the
+            // end of RAM
+            Bcd { x } => {
+                let vx = self.v_reg[x];
+                let i = self.i_reg as usize;
+                self.write_byte(i, vx / 100);
+                self.write_byte(i + 1, (vx / 10) % 10);
+                self.write_byte(i + 2, vx % 10);
+            },
+            // FX55 - STORE V0 - VX - Store V0 through VX into RAM starting at I, wrapping past the
+            // end of RAM
+            Store { x } => {
+                let i = self.i_reg as usize;
+                for idx in 0..=x {
+                    self.write_byte(i + idx, self.v_reg[idx]);
+                }
+                if self.config.load_store_increments_i {
+                    self.i_reg = self.i_reg.wrapping_add(x as u16 + 1);
+                }
+            },
+            // FX75 - STORE FLAGS - Store V0 through VX into the RPL flags, X <= 7 (SCHIP)
+            StoreFlags { x } => {
+                let x = x.min(NUM_RPL_FLAGS - 1);
+                self.rpl_flags[..=x].copy_from_slice(&self.v_reg[..=x]);
+            },
+            // FX85 - LOAD FLAGS - Load V0 through VX from the RPL flags, X <= 7 (SCHIP)
+            LoadFlags { x } => {
+                let x = x.min(NUM_RPL_FLAGS - 1);
+                self.v_reg[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+            },
+            // FX65 - LOAD V0 - VX - Load V0 through VX from RAM starting at I, wrapping like FX55
+            Load { x } => {
+                let i = self.i_reg as usize;
+                for idx in 0..=x {
+                    self.v_reg[idx] = self.ram[(i + idx) % self.ram.len()];
+                }
+                if self.config.load_store_increments_i {
+                    self.i_reg = self.i_reg.wrapping_add(x as u16 + 1);
+                }
+            },
+            Unknown(_) => match self.config.on_unknown_opcode {
+                UnknownOpcode::Panic => panic!("unknown opcode {:04X}", op),
+                UnknownOpcode::Nop => (),
+                UnknownOpcode::Error => return Err(Chip8Error::UnknownOpcode(op)),
+            },
+        }
+
+        Ok(())
+    }
+
+    // The one place the 8XY4 - 8XYE results land. VX is written before VF, so when X is F the
+    // flag is what's left in VF rather than the result
+    fn set_result_and_flag(&mut self, x: usize, result: u8, flag: bool) {
+        self.v_reg[x] = result;
+        self.v_reg[0xF] = flag as u8;
+    }
+
+    fn plane_selected(&self, plane: usize) -> bool {
+        self.plane_mask & (1 << plane) != 0
+    }
+
+    // Moves every row of the selected planes down n pixels, blanking the rows at the top
+    fn scroll_down(&mut self, n: usize) {
+        let height = self.display_height();
+        let n = n.min(height);
+        for plane in 0..NUM_PLANES {
+            if !self.plane_selected(plane) {
+                continue;
             }
+            let rows = &mut self.planes[plane];
+            rows.copy_within(..height - n, n);
+            rows[..n].fill(0);
         }
+        self.dirty_rows = u64::MAX;
     }
-    
-    // not public since only called internally
-    fn fetch (&mut self) -> u16 {
+
+    // Moves every column of the selected planes right n pixels, blanking the left edge
+    fn scroll_right(&mut self, n: usize) {
+        let (height, row_mask) = (self.display_height(), self.row_mask());
+        for plane in 0..NUM_PLANES {
+            if !self.plane_selected(plane) {
+                continue;
+            }
+            for row in self.planes[plane][..height].iter_mut() {
+                *row = row.checked_shl(n as u32).unwrap_or(0) & row_mask;
+            }
+        }
+        self.dirty_rows = u64::MAX;
+    }
+
+    // Moves every column of the selected planes left n pixels, blanking the right edge
+    fn scroll_left(&mut self, n: usize) {
+        let height = self.display_height();
+        for plane in 0..NUM_PLANES {
+            if !self.plane_selected(plane) {
+                continue;
+            }
+            for row in self.planes[plane][..height].iter_mut() {
+                *row = row.checked_shr(n as u32).unwrap_or(0);
+            }
+        }
+        self.dirty_rows = u64::MAX;
+    }
+
+    // Every opcode that writes to RAM goes through here so watchpoints see all writes. Addresses
+    // past the end of RAM wrap back around to 0 like DXYN's sprite reads do
+    fn write_byte(&mut self, addr: usize, val: u8) {
+        let addr = addr % self.ram.len();
+        self.ram[addr] = val;
+        if let Some(callback) = self.watchpoints.get_mut(&(addr as u16)) {
+            callback(addr as u16, val);
+        }
+    }
+
+    // The opcode tick would run next, without moving pc. Comes back as 0 when pc is too close
+    // to the end of RAM for a whole word, where tick would fail with PcOutOfBounds
+    pub fn peek_opcode(&self) -> u16 {
+        let pc = self.pc as usize;
+        if pc + 1 >= self.ram.len() {
+            return 0;
+        }
         // fetch these two parts as u16 to enable shifting to left of hbyte and then bitwise OR the
         // lbyte into where the hbyte used to be
-        let higher_byte = self.ram[self.pc as usize] as u16;
-        let lower_byte = self.ram[(self.pc + 1) as usize] as u16;
+        let higher_byte = self.ram[pc] as u16;
+        let lower_byte = self.ram[pc + 1] as u16;
         // shift higher_byte 8 bits to the left
-        let op = (higher_byte << 8) | lower_byte; 
-        self.pc += 2;
-        op
+        (higher_byte << 8) | lower_byte
+    }
+
+    // not public since only called internally
+    fn fetch (&mut self) -> Result<u16, Chip8Error> {
+        if self.pc as usize + 1 >= self.ram.len() {
+            return Err(Chip8Error::PcOutOfBounds);
+        }
+        let op = self.peek_opcode();
+        // wrapping so an instruction in the last word of 64KB RAM doesn't overflow pc
+        self.pc = self.pc.wrapping_add(2);
+        Ok(op)
+    }
+
+    // Read-only views of the CPU state for debuggers
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i_reg(&self) -> u16 {
+        self.i_reg
+    }
+
+    // Points I at `val` for debuggers and memory editors. Masked to 12 bits with the classic
+    // 4KB of RAM, XO-CHIP's 64KB uses all 16
+    pub fn set_i(&mut self, val: u16) {
+        self.i_reg = if self.ram.len() > RAM_SIZE { val } else { val & 0xFFF };
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    // Value of V register `idx`, panics if idx is past VF
+    pub fn v_reg(&self, idx: usize) -> u8 {
+        self.v_reg[idx]
+    }
+
+    pub fn registers(&self) -> &[u8; NUM_REGS] {
+        &self.v_reg
+    }
+
+    pub fn dt(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn st(&self) -> u8 {
+        self.st
+    }
+
+    // Read-only view of all of RAM for memory viewers
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    // Byte at `addr`, or None when addr is past the end of RAM
+    pub fn read_byte(&self, addr: usize) -> Option<u8> {
+        self.ram.get(addr).copied()
+    }
+
+    // SCHIP RPL user flags, for frontends that persist them to disk
+    pub fn rpl_flags(&self) -> &[u8; NUM_RPL_FLAGS] {
+        &self.rpl_flags
+    }
+
+    // Cycles used since construction or the last reset, the cycle_cost of every instruction
+    // executed. FX0A counts again for every tick it spends waiting
+    pub fn cycle_count(&self) -> u64 {
+        self.cycles
+    }
+
+    // Collision result of the last sprite draw, matching the VF it set (any non-zero VF in
+    // hires). False until something is drawn
+    pub fn last_draw_collided(&self) -> bool {
+        self.last_draw_collided
+    }
+
+    // Freezes the CPU, tick returns without running anything until resume. The timers keep
+    // going unless config.freeze_timers_on_pause is set
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // true once 00FD has stopped the interpreter, frontends can end their loop
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // true while the CPU is blocked on FX0A
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key
+    }
+
+    // Whether a DXYN would draw now under display_wait_quirk rather than wait, true when no
+    // instruction has run since the last frame boundary. tick_timers sets it, frontends that
+    // run the CPU and the display on separate clocks can set it themselves at vblank instead
+    pub fn frame_ready(&self) -> bool {
+        self.frame_ready
+    }
+
+    pub fn set_frame_ready(&mut self, ready: bool) {
+        self.frame_ready = ready;
+    }
+
+    // true while the sound timer is running, frontends poll this each frame to drive audio
+    pub fn is_beeping(&self) -> bool {
+        self.st > 0
     }
 
     pub fn tick_timers(&mut self) {
+        if self.paused && self.config.freeze_timers_on_pause {
+            return;
+        }
         if self.dt > 0 { 
             self.dt -= 1;
         }
 
-        if self.st > 0 {
-            if self.st == 1 {
-                // BEEP
+        // a new frame, a DXYN waiting on the display can go ahead
+        self.frame_ready = true;
+
+        // BEEP - tell the sound callback whenever the beep starts or stops, a sound timer set
+        // to N beeps for N ticks
+        let beeping = self.st > 0;
+        if beeping != self.sound_on {
+            self.sound_on = beeping;
+            if let Some(callback) = self.sound_callback.as_mut() {
+                callback(beeping);
             }
+        }
+
+        if self.st > 0 {
             self.st -= 1;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn ret_returns_to_caller() {
+        let mut emu = Emu::new();
+        emu.execute(0x2300).unwrap();
+        assert_eq!(emu.pc, 0x300);
+        emu.execute(0x00EE).unwrap();
+        assert_eq!(emu.pc, START_ADDR);
+    }
+
+    #[test]
+    fn or_sets_vx_to_bitwise_or() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x0F;
+        emu.v_reg[1] = 0xF0;
+        emu.execute(0x8011).unwrap();
+        assert_eq!(emu.v_reg[0], 0xFF);
+    }
+
+    #[test]
+    fn sub_sets_vf_when_no_borrow() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x0A;
+        emu.v_reg[1] = 0x05;
+        emu.execute(0x8015).unwrap();
+        assert_eq!(emu.v_reg[0], 0x05);
+        assert_eq!(emu.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn sub_clears_vf_on_borrow() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x05;
+        emu.v_reg[1] = 0x0A;
+        emu.execute(0x8015).unwrap();
+        assert_eq!(emu.v_reg[0], 0xFB);
+        assert_eq!(emu.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn reverse_sub_sets_vx_to_vy_minus_vx() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x05;
+        emu.v_reg[1] = 0x0A;
+        emu.execute(0x8017).unwrap();
+        assert_eq!(emu.v_reg[0], 0x05);
+        assert_eq!(emu.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn shift_right_in_place() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0b0000_0011;
+        emu.v_reg[1] = 0b1000_0000;
+        emu.execute(0x8016).unwrap();
+        assert_eq!(emu.v_reg[0], 0b0000_0001);
+        assert_eq!(emu.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn shift_right_from_vy() {
+        let mut emu = Emu::new();
+        emu.config.shift_quirk = false;
+        emu.v_reg[0] = 0b0000_0011;
+        emu.v_reg[1] = 0b1000_0000;
+        emu.execute(0x8016).unwrap();
+        assert_eq!(emu.v_reg[0], 0b0100_0000);
+        assert_eq!(emu.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn shift_left_in_place() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0b1000_0001;
+        emu.v_reg[1] = 0b0000_0001;
+        emu.execute(0x801E).unwrap();
+        assert_eq!(emu.v_reg[0], 0b0000_0010);
+        assert_eq!(emu.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn shift_left_from_vy() {
+        let mut emu = Emu::new();
+        emu.config.shift_quirk = false;
+        emu.v_reg[0] = 0b1000_0001;
+        emu.v_reg[1] = 0b0000_0001;
+        emu.execute(0x801E).unwrap();
+        assert_eq!(emu.v_reg[0], 0b0000_0010);
+        assert_eq!(emu.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn flag_wins_when_vf_is_the_destination() {
+        // (opcode with X = F, VF, VY, VF afterwards), every result differs from its flag
+        let cases = [
+            (0x8F14, 0xFF, 0x03, 1), // 0x02 with carry
+            (0x8F14, 0x01, 0x02, 0), // 0x03
+            (0x8F15, 0x05, 0x03, 1), // 0x02
+            (0x8F15, 0x03, 0x05, 0), // 0xFE with borrow
+            (0x8F17, 0x03, 0x05, 1), // 0x02
+            (0x8F17, 0x05, 0x03, 0), // 0xFE with borrow
+            (0x8F16, 0x07, 0x07, 1), // 0x03
+            (0x8F16, 0x06, 0x06, 0), // 0x03
+            (0x8F1E, 0x81, 0x81, 1), // 0x02
+            (0x8F1E, 0x41, 0x41, 0), // 0x82
+        ];
+        // VY matches VF for the shifts so both shift_quirk settings see the same source
+        for shift_quirk in [true, false] {
+            for (op, vf, vy, flag) in cases {
+                let mut emu = Emu::new();
+                emu.config.shift_quirk = shift_quirk;
+                emu.v_reg[0xF] = vf;
+                emu.v_reg[1] = vy;
+                emu.execute(op).unwrap();
+                assert_eq!(emu.v_reg[0xF], flag, "{:04X} with VF = {:#04X}", op, vf);
+            }
+        }
+    }
+
+    #[test]
+    fn flag_sources_are_read_before_vf_changes() {
+        // (opcode with Y = F, VX, VF, VX and VF afterwards), VY is the old VF throughout
+        let cases = [
+            (0x81F4, 0xFF, 0x03, 0x02, 1),
+            (0x81F5, 0x03, 0x05, 0xFE, 0),
+            (0x81F7, 0x03, 0x05, 0x02, 1),
+            (0x81F6, 0x00, 0x07, 0x03, 1),
+            (0x81FE, 0x00, 0x41, 0x82, 0),
+        ];
+        for (op, vx, vf, result, flag) in cases {
+            let mut emu = Emu::new();
+            // the shifts read VY here
+            emu.config.shift_quirk = false;
+            emu.v_reg[1] = vx;
+            emu.v_reg[0xF] = vf;
+            emu.execute(op).unwrap();
+            assert_eq!((emu.v_reg[1], emu.v_reg[0xF]), (result, flag), "{:04X}", op);
+        }
+    }
+
+    #[test]
+    fn set_index() {
+        let mut emu = Emu::new();
+        emu.execute(0xA123).unwrap();
+        assert_eq!(emu.i_reg, 0x123);
+    }
+
+    #[test]
+    fn jump_with_v0_offset() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x02;
+        emu.v_reg[3] = 0x10;
+        emu.execute(0xB300).unwrap();
+        assert_eq!(emu.pc, 0x302);
+    }
+
+    #[test]
+    fn jump_with_vx_offset() {
+        let mut emu = Emu::new();
+        emu.config.jump_quirk = true;
+        emu.v_reg[0] = 0x02;
+        emu.v_reg[3] = 0x10;
+        emu.execute(0xB300).unwrap();
+        assert_eq!(emu.pc, 0x310);
+    }
+
+    #[test]
+    fn random_is_masked_with_nn() {
+        let mut emu = Emu::with_rng(|| 0xAB);
+        emu.execute(0xC30F).unwrap();
+        assert_eq!(emu.v_reg[3], 0x0B);
+    }
+
+    // Reads back row `y` of the screen from `x` as a byte, MSB first, for comparing with sprites
+    fn screen_byte(emu: &Emu, x: usize, y: usize) -> u8 {
+        (0..8).fold(0, |byte, col| {
+            let on = emu.get_pixel((x + col) % SCREEN_WIDTH, y);
+            (byte << 1) | on as u8
+        })
+    }
+
+    #[test]
+    fn draw_font_glyph() {
+        let mut emu = Emu::new();
+        // glyph 1 is at I = 5
+        emu.i_reg = 5;
+        emu.v_reg[0] = 2;
+        emu.v_reg[1] = 3;
+        emu.execute(0xD015).unwrap();
+        for row in 0..5 {
+            assert_eq!(screen_byte(&emu, 2, 3 + row), FONTSET[5 + row]);
+        }
+        assert!(!emu.get_pixel(0, 0));
+        assert_eq!(emu.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn draw_sets_vf_on_collision() {
+        let mut emu = Emu::new();
+        emu.execute(0xD005).unwrap();
+        assert_eq!(emu.v_reg[0xF], 0);
+        // drawing the same sprite again erases it
+        emu.execute(0xD005).unwrap();
+        assert_eq!(emu.v_reg[0xF], 1);
+        assert!(emu.get_display().all(|p| !p));
+    }
+
+    #[test]
+    fn clear_display_blanks_the_screen() {
+        let mut emu = Emu::new();
+        // the 0 glyph at the top left
+        emu.execute(0xD005).unwrap();
+        assert!(emu.get_display().any(|on| on));
+        emu.take_dirty_rows();
+        emu.clear_display();
+        assert!(emu.get_display().all(|on| !on));
+        assert_eq!(emu.take_dirty_rows().len(), SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn ram_writes_wrap_around() {
+        let mut emu = Emu::new();
+        emu.write_byte(RAM_SIZE + 3, 0xAB);
+        assert_eq!(emu.ram[3], 0xAB);
+        assert_eq!(emu.ram.len(), RAM_SIZE);
+    }
+
+    #[test]
+    fn set_i_moves_the_sprite_source() {
+        let mut emu = Emu::new();
+        emu.ram[0x345] = 0b1010_0000;
+        // the top nibble doesn't fit in 4KB
+        emu.set_i(0xF345);
+        assert_eq!(emu.i_reg(), 0x345);
+        emu.execute(0xD001).unwrap();
+        assert!(emu.get_pixel(0, 0) && !emu.get_pixel(1, 0) && emu.get_pixel(2, 0));
+
+        let mut emu = Emu::with_config(Chip8Config { ram_size: 0x10000, ..Chip8Config::default() });
+        emu.set_i(0xF345);
+        assert_eq!(emu.i_reg(), 0xF345);
+    }
+
+    #[test]
+    fn draw_wraps_sprite_reads_around_ram() {
+        let mut emu = Emu::new();
+        emu.ram[0xFFE] = 0xFF;
+        emu.ram[0xFFF] = 0x81;
+        emu.i_reg = 0x0FFE;
+        // DRW V0, V0, 10 - the last 8 rows come from the 0 and 1 glyphs at address 0
+        emu.execute(0xD00A).unwrap();
+        let rows: Vec<u8> = (0..10)
+            .map(|y| (0..8).fold(0, |row, x| row << 1 | emu.get_pixel(x, y) as u8))
+            .collect();
+        assert_eq!(rows, [0xFF, 0x81, 0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20]);
+
+        // a 16x16 sprite wraps between the two bytes of a row too
+        emu.execute(0x00E0).unwrap();
+        emu.execute(0x00FF).unwrap();
+        emu.i_reg = 0x0FFF;
+        emu.execute(0xD000).unwrap();
+        assert!(emu.get_pixel(0, 0) && emu.get_pixel(7, 0));
+        assert!(emu.get_pixel(8, 0) && !emu.get_pixel(12, 0));
+    }
+
+    #[test]
+    fn draw_clips_at_bottom_right() {
+        let mut emu = Emu::new();
+        // glyph 8 (0xF0, 0x90, ...) drawn two pixels from the right and bottom edges
+        emu.i_reg = 8 * 5;
+        emu.v_reg[0] = (SCREEN_WIDTH - 2) as u8;
+        emu.v_reg[1] = (SCREEN_HEIGHT - 2) as u8;
+        emu.execute(0xD015).unwrap();
+        assert_eq!(emu.get_display().filter(|&p| p).count(), 3);
+        assert!(emu.get_pixel(SCREEN_WIDTH - 2, SCREEN_HEIGHT - 2));
+        assert!(emu.get_pixel(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 2));
+        assert!(emu.get_pixel(SCREEN_WIDTH - 2, SCREEN_HEIGHT - 1));
+        assert!(!emu.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn draw_wraps_at_bottom_right() {
+        let mut emu = Emu::new();
+        emu.config.clip_sprites = false;
+        emu.i_reg = 8 * 5;
+        emu.v_reg[0] = (SCREEN_WIDTH - 2) as u8;
+        emu.v_reg[1] = (SCREEN_HEIGHT - 2) as u8;
+        emu.execute(0xD015).unwrap();
+        // the top row of the glyph wraps its right half onto the left edge
+        assert_eq!(screen_byte(&emu, SCREEN_WIDTH - 2, SCREEN_HEIGHT - 2), 0xF0);
+        // the third row of the glyph wraps onto the top of the screen
+        assert_eq!(screen_byte(&emu, SCREEN_WIDTH - 2, 0), 0xF0);
+    }
+
+    #[test]
+    fn draw_start_coordinates_wrap_when_clipping() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = (SCREEN_WIDTH + 1) as u8;
+        emu.v_reg[1] = (SCREEN_HEIGHT + 1) as u8;
+        emu.execute(0xD015).unwrap();
+        assert_eq!(screen_byte(&emu, 1, 1), FONTSET[0]);
+    }
+
+    #[test]
+    fn skip_if_registers_differ() {
+        let mut emu = Emu::new();
+        emu.v_reg[1] = 0x12;
+        emu.v_reg[2] = 0x12;
+        emu.execute(0x9120).unwrap();
+        assert_eq!(emu.pc, START_ADDR);
+        emu.v_reg[2] = 0x13;
+        emu.execute(0x9120).unwrap();
+        assert_eq!(emu.pc, START_ADDR + 2);
+    }
+
+    #[test]
+    fn skip_if_key_pressed() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x4;
+        emu.execute(0xE09E).unwrap();
+        assert_eq!(emu.pc, START_ADDR);
+        emu.keys[0x4] = true;
+        emu.execute(0xE09E).unwrap();
+        assert_eq!(emu.pc, START_ADDR + 2);
+    }
+
+    #[test]
+    fn skip_if_key_not_pressed() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x4;
+        emu.keys[0x4] = true;
+        emu.execute(0xE0A1).unwrap();
+        assert_eq!(emu.pc, START_ADDR);
+        emu.keys[0x4] = false;
+        emu.execute(0xE0A1).unwrap();
+        assert_eq!(emu.pc, START_ADDR + 2);
+    }
+
+    #[test]
+    fn key_skip_ignores_out_of_range_vx() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x10;
+        emu.execute(0xE09E).unwrap();
+        assert_eq!(emu.pc, START_ADDR);
+        emu.execute(0xE0A1).unwrap();
+        assert_eq!(emu.pc, START_ADDR + 2);
+    }
+
+    #[test]
+    fn wait_for_key_blocks_until_pressed() {
+        let mut emu = Emu::new();
+        emu.ram[START_ADDR as usize] = 0xF3;
+        emu.ram[START_ADDR as usize + 1] = 0x0A;
+        emu.tick().unwrap();
+        assert_eq!(emu.pc, START_ADDR);
+        assert!(emu.is_waiting_for_key());
+        emu.keys[0x7] = true;
+        emu.tick().unwrap();
+        assert_eq!(emu.pc, START_ADDR + 2);
+        assert_eq!(emu.v_reg[3], 0x7);
+        assert!(!emu.is_waiting_for_key());
+    }
+
+    #[test]
+    fn delay_timer_round_trip() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 10;
+        emu.execute(0xF015).unwrap();
+        emu.tick_timers();
+        emu.execute(0xF107).unwrap();
+        assert_eq!(emu.v_reg[1], 9);
+    }
+
+    #[test]
+    fn set_sound_timer() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 2;
+        emu.execute(0xF018).unwrap();
+        assert_eq!(emu.st, 2);
+        emu.tick_timers();
+        emu.tick_timers();
+        emu.tick_timers();
+        assert_eq!(emu.st, 0);
+    }
+
+    #[test]
+    fn add_to_index() {
+        let mut emu = Emu::new();
+        emu.i_reg = 0x0FFF;
+        emu.v_reg[0] = 0x02;
+        emu.v_reg[0xF] = 0x55;
+        emu.execute(0xF01E).unwrap();
+        assert_eq!(emu.i_reg, 0x1001);
+        assert_eq!(emu.v_reg[0xF], 0x55);
+    }
+
+    #[test]
+    fn add_to_index_overflow_sets_vf() {
+        let mut emu = Emu::new();
+        emu.config.i_overflow_quirk = true;
+        emu.i_reg = 0x0FF0;
+        emu.v_reg[0] = 0x02;
+        emu.execute(0xF01E).unwrap();
+        assert_eq!(emu.v_reg[0xF], 0);
+        emu.v_reg[0] = 0x10;
+        emu.execute(0xF01E).unwrap();
+        assert_eq!(emu.i_reg, 0x1002);
+        assert_eq!(emu.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn font_address() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0xA;
+        emu.execute(0xF029).unwrap();
+        assert_eq!(emu.i_reg, 50);
+        emu.v_reg[1] = 0;
+        emu.execute(0xD115).unwrap();
+        for row in 0..5 {
+            assert_eq!(screen_byte(&emu, 0, row), FONTSET[50 + row]);
+        }
+    }
+
+    #[test]
+    fn binary_coded_decimal() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 156;
+        emu.i_reg = 0x300;
+        emu.execute(0xF033).unwrap();
+        assert_eq!(emu.ram[0x300..0x303], [1, 5, 6]);
+    }
+
+    #[test]
+    fn binary_coded_decimal_wraps_at_end_of_ram() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 123;
+        emu.i_reg = 0xFFE;
+        emu.execute(0xF033).unwrap();
+        assert_eq!(emu.ram[0xFFE..], [1, 2]);
+        assert_eq!(emu.ram[0], 3);
+        assert_eq!(emu.i_reg, 0xFFE);
+    }
+
+    #[test]
+    fn store_and_load_registers() {
+        let mut emu = Emu::new();
+        emu.v_reg[..4].copy_from_slice(&[1, 2, 3, 4]);
+        emu.i_reg = 0x300;
+        emu.execute(0xF355).unwrap();
+        assert_eq!(emu.ram[0x300..0x305], [1, 2, 3, 4, 0]);
+        assert_eq!(emu.i_reg, 0x304);
+
+        emu.v_reg = [0; NUM_REGS];
+        emu.i_reg = 0x300;
+        emu.execute(0xF365).unwrap();
+        assert_eq!(emu.v_reg[..5], [1, 2, 3, 4, 0]);
+        assert_eq!(emu.i_reg, 0x304);
+    }
+
+    #[test]
+    fn store_and_load_wrap_at_end_of_ram() {
+        let mut emu = Emu::new();
+        emu.v_reg[..2].copy_from_slice(&[7, 8]);
+        emu.i_reg = 0xFFF;
+        emu.execute(0xF155).unwrap();
+        assert_eq!((emu.ram[0xFFF], emu.ram[0]), (7, 8));
+        assert_eq!(emu.i_reg, 0x1001);
+
+        emu.v_reg = [0; NUM_REGS];
+        emu.i_reg = 0xFFF;
+        emu.execute(0xF165).unwrap();
+        assert_eq!(emu.v_reg[..2], [7, 8]);
+
+        // with 64KB of RAM I itself wraps past 0xFFFF
+        let mut emu = Emu::with_config(Chip8Config { ram_size: 0x10000, ..Chip8Config::default() });
+        emu.i_reg = 0xFFFF;
+        emu.execute(0xF155).unwrap();
+        assert_eq!(emu.i_reg, 0x0001);
+        emu.i_reg = 0xFFFF;
+        emu.execute(0xF165).unwrap();
+        assert_eq!(emu.i_reg, 0x0001);
+    }
+
+    #[test]
+    fn store_and_load_registers_keep_i() {
+        let mut emu = Emu::new();
+        emu.config.load_store_increments_i = false;
+        emu.v_reg[..4].copy_from_slice(&[1, 2, 3, 4]);
+        emu.i_reg = 0x300;
+        emu.execute(0xF355).unwrap();
+        assert_eq!(emu.i_reg, 0x300);
+
+        emu.v_reg = [0; NUM_REGS];
+        emu.execute(0xF365).unwrap();
+        assert_eq!(emu.v_reg[..4], [1, 2, 3, 4]);
+        assert_eq!(emu.i_reg, 0x300);
+    }
+
+    #[test]
+    fn call_past_stack_size_overflows() {
+        let mut emu = Emu::new();
+        for _ in 0..STACK_SIZE {
+            emu.execute(0x2200).unwrap();
+        }
+        assert_eq!(emu.execute(0x2200), Err(Chip8Error::StackOverflow));
+        assert_eq!(emu.sp as usize, STACK_SIZE);
+    }
+
+    #[test]
+    fn ret_with_empty_stack_underflows() {
+        let mut emu = Emu::new();
+        assert_eq!(emu.execute(0x00EE), Err(Chip8Error::StackUnderflow));
+        assert_eq!(emu.sp, 0);
+        assert_eq!(emu.pc, START_ADDR);
+    }
+
+    #[test]
+    fn fetch_past_end_of_ram_errors() {
+        let mut emu = Emu::new();
+        emu.pc = 0x0FFF;
+        assert_eq!(emu.tick(), Err(Chip8Error::PcOutOfBounds));
+        assert_eq!(emu.pc, 0x0FFF);
+        emu.pc = 0x1000;
+        assert_eq!(emu.tick(), Err(Chip8Error::PcOutOfBounds));
+    }
+
+    #[test]
+    fn stalls_and_skips_at_top_of_address_space() {
+        let mut emu = Emu::with_config(Chip8Config {
+            ram_size: 0x10000,
+            display_wait_quirk: true,
+            ..Chip8Config::default()
+        });
+        // LD V0, K in the last word waits without pc underflowing after the wrap to 0
+        emu.ram[0xFFFE..].copy_from_slice(&[0xF0, 0x0A]);
+        emu.pc = 0xFFFE;
+        emu.tick().unwrap();
+        assert_eq!(emu.pc, 0xFFFE);
+
+        // so does a DXYN waiting for the next frame
+        emu.ram[0xFFFE..].copy_from_slice(&[0xD0, 0x05]);
+        emu.set_frame_ready(false);
+        emu.tick().unwrap();
+        assert_eq!(emu.pc, 0xFFFE);
+
+        // SE V0, 0 skips over the wrap
+        emu.ram[0xFFFE..].copy_from_slice(&[0x30, 0x00]);
+        emu.tick().unwrap();
+        assert_eq!(emu.pc, 0x0002);
+    }
+
+    #[test]
+    fn peek_opcode_leaves_pc() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x60, 0x2A, 0x12, 0x00]).unwrap();
+        assert_eq!(emu.peek_opcode(), 0x602A);
+        assert_eq!(emu.peek_opcode(), 0x602A);
+        assert_eq!(emu.pc, 0x200);
+        assert_eq!(emu.tick(), Ok(0x602A));
+        assert_eq!(emu.peek_opcode(), 0x1200);
+
+        emu.pc = 0x0FFE;
+        emu.ram[0x0FFE..].copy_from_slice(&[0xAB, 0xCD]);
+        assert_eq!(emu.peek_opcode(), 0xABCD);
+        emu.pc = 0x0FFF;
+        assert_eq!(emu.peek_opcode(), 0);
+        assert_eq!(emu.pc, 0x0FFF);
+    }
+
+    #[test]
+    fn load_rom_copies_to_start_addr() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x12, 0x34, 0x56]).unwrap();
+        let start = START_ADDR as usize;
+        assert_eq!(emu.ram[start..start + 4], [0x12, 0x34, 0x56, 0x00]);
+        assert_eq!(emu.ram[start - 1], 0x00);
+    }
+
+    #[test]
+    fn load_rom_rejects_oversized_rom() {
+        let mut emu = Emu::new();
+        let max = RAM_SIZE - START_ADDR as usize;
+        assert!(emu.load_rom(&vec![0xFF; max]).is_ok());
+        assert_eq!(
+            emu.load_rom(&vec![0xFF; max + 1]),
+            Err(Chip8Error::RomTooLarge { size: max + 1, max })
+        );
+    }
+
+    #[test]
+    fn errors_display_for_users() {
+        let err = Chip8Error::RomTooLarge { size: 4000, max: 3584 };
+        assert_eq!(err.to_string(), "ROM is 4000 bytes but only 3584 fit in RAM");
+        assert_eq!(Chip8Error::UnknownOpcode(0x5121).to_string(), "unknown opcode 5121");
+        // usable anywhere a boxed std error is expected
+        let boxed: Box<dyn std::error::Error> = Box::new(Chip8Error::StackUnderflow);
+        assert_eq!(boxed.to_string(), "return with an empty call stack");
+    }
+
+    #[test]
+    fn keypress_drives_key_skip() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0xA;
+        emu.keypress(0xA, true);
+        emu.execute(0xE09E).unwrap();
+        assert_eq!(emu.pc, START_ADDR + 2);
+        emu.keypress(0xA, false);
+        emu.execute(0xE09E).unwrap();
+        assert_eq!(emu.pc, START_ADDR + 2);
+        // out of range keys are ignored
+        emu.keypress(NUM_KEYS, true);
+        assert!(emu.keys.iter().all(|&k| !k));
+    }
+
+    #[test]
+    fn reads_key_state() {
+        let mut emu = Emu::new();
+        emu.keypress(0x5, true);
+        assert!(emu.is_key_pressed(5));
+        assert!((0..NUM_KEYS).filter(|&key| key != 5).all(|key| !emu.is_key_pressed(key)));
+        assert!(!emu.is_key_pressed(NUM_KEYS));
+    }
+
+    #[test]
+    fn clear_keys_releases_everything() {
+        let mut emu = Emu::new();
+        for key in [0x0, 0x5, 0xF] {
+            emu.keypress(key, true);
+        }
+        emu.clear_keys();
+        assert_eq!(emu.keys, [false; NUM_KEYS]);
+        // LD V0, K waits again instead of seeing a stuck key
+        emu.load_rom(&[0xF0, 0x0A]).unwrap();
+        emu.tick().unwrap();
+        assert!(emu.is_waiting_for_key());
+    }
+
+    #[test]
+    fn beeps_until_sound_timer_expires() {
+        let mut emu = Emu::new();
+        assert!(!emu.is_beeping());
+        emu.v_reg[0] = 2;
+        emu.execute(0xF018).unwrap();
+        assert!(emu.is_beeping());
+        emu.tick_timers();
+        assert!(emu.is_beeping());
+        emu.tick_timers();
+        assert!(!emu.is_beeping());
+    }
+
+    #[test]
+    fn tick_returns_executed_opcode() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x60, 0x2A, 0xA1, 0x23]).unwrap();
+        assert_eq!(emu.tick(), Ok(0x602A));
+        assert_eq!(emu.tick(), Ok(0xA123));
+        assert_eq!(emu.v_reg[0], 0x2A);
+    }
+
+    #[test]
+    fn with_config_applies_quirks() {
+        let mut emu = Emu::with_config(Chip8Config::schip());
+        emu.v_reg[0] = 0x02;
+        emu.v_reg[3] = 0x10;
+        emu.execute(0xB300).unwrap();
+        assert_eq!(emu.pc, 0x310);
+    }
+
+    #[test]
+    fn logical_ops_keep_vf() {
+        for op in [0x8011, 0x8012, 0x8013] {
+            let mut emu = Emu::new();
+            emu.v_reg[0xF] = 0x55;
+            emu.execute(op).unwrap();
+            assert_eq!(emu.v_reg[0xF], 0x55);
+        }
+    }
+
+    #[test]
+    fn logical_ops_reset_vf() {
+        for op in [0x8011, 0x8012, 0x8013] {
+            let mut emu = Emu::new();
+            emu.config.vf_reset_quirk = true;
+            emu.v_reg[0xF] = 0x55;
+            emu.execute(op).unwrap();
+            assert_eq!(emu.v_reg[0xF], 0);
+        }
+    }
+
+    #[test]
+    fn run_frame_ticks_timers_once() {
+        for ticks_per_frame in [0, 1, 10, 50] {
+            let config = Chip8Config { ticks_per_frame, ..Chip8Config::default() };
+            let mut emu = Emu::with_config(config);
+            emu.dt = 10;
+            emu.st = 10;
+            emu.run_frame().unwrap();
+            assert_eq!(emu.dt, 9);
+            assert_eq!(emu.st, 9);
+            // every RAM byte after the fontset is a 0000 NOP
+            assert_eq!(emu.pc, START_ADDR + 2 * ticks_per_frame as u16);
+        }
+    }
+
+    #[test]
+    fn set_speed_changes_ticks_per_frame() {
+        let mut emu = Emu::new();
+        assert_eq!(emu.config.ticks_per_frame, 10);
+        emu.set_speed(25);
+        let ticks = Rc::new(RefCell::new(0));
+        let counter = ticks.clone();
+        emu.set_trace_hook(Box::new(move |_, _| *counter.borrow_mut() += 1));
+        emu.run_frame().unwrap();
+        assert_eq!(*ticks.borrow(), 25);
+        assert_eq!(emu.pc, START_ADDR + 2 * 25);
+    }
+
+    #[test]
+    fn step_one_frame() {
+        let mut emu = Emu::new();
+        emu.dt = 100;
+        emu.step(Duration::from_nanos(16_666_667)).unwrap();
+        assert_eq!(emu.dt, 99);
+        assert_eq!(emu.pc, START_ADDR + 2 * 10);
+    }
+
+    #[test]
+    fn step_accumulates_small_slices() {
+        let mut emu = Emu::new();
+        emu.dt = 100;
+        emu.step(Duration::from_millis(10)).unwrap();
+        assert_eq!(emu.dt, 100);
+        for _ in 0..99 {
+            emu.step(Duration::from_millis(10)).unwrap();
+        }
+        // 1s in total is 60 timer ticks and 600 cycles
+        assert_eq!(emu.dt, 40);
+        assert_eq!(emu.pc, START_ADDR + 2 * 600);
+    }
+
+    #[test]
+    fn step_large_duration() {
+        let config = Chip8Config { ticks_per_frame: 1, ..Chip8Config::default() };
+        let mut emu = Emu::with_config(config);
+        emu.dt = 200;
+        emu.st = 200;
+        emu.step(Duration::from_millis(2500)).unwrap();
+        assert_eq!(emu.dt, 50);
+        assert_eq!(emu.st, 50);
+        assert_eq!(emu.pc, START_ADDR + 2 * 150);
+    }
+
+    #[test]
+    fn resolution_follows_hires() {
+        let mut emu = Emu::new();
+        assert_eq!(emu.resolution(), (64, 32));
+        emu.execute(0x00FF).unwrap();
+        assert_eq!(emu.resolution(), (128, 64));
+        emu.execute(0x00FE).unwrap();
+        assert_eq!(emu.resolution(), (64, 32));
+    }
+
+    #[test]
+    fn switch_resolution() {
+        let mut emu = Emu::new();
+        assert_eq!((emu.display_width(), emu.display_height()), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(emu.get_display().count(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        emu.execute(0xD005).unwrap();
+
+        emu.execute(0x00FF).unwrap();
+        assert_eq!((emu.display_width(), emu.display_height()), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_eq!(emu.get_display().count(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert!(emu.get_display().all(|p| !p));
+
+        // hires sprites can reach past the lo-res edges
+        emu.v_reg[0] = 100;
+        emu.v_reg[1] = 50;
+        emu.execute(0xD015).unwrap();
+        assert!(emu.get_display().nth(100 + HIRES_WIDTH * 50).unwrap());
+
+        emu.execute(0x00FE).unwrap();
+        assert_eq!((emu.display_width(), emu.display_height()), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert!(emu.get_display().all(|p| !p));
+    }
+
+    #[test]
+    fn scroll_down() {
+        let mut emu = Emu::new();
+        emu.toggle_pixel(5, 3);
+        emu.execute(0x00C2).unwrap();
+        assert_eq!(emu.get_display().filter(|&p| p).count(), 1);
+        assert!(emu.get_pixel(5, 5));
+        // scrolling past the bottom edge drops the pixel
+        emu.execute(0x00CF).unwrap();
+        emu.execute(0x00CF).unwrap();
+        assert!(emu.get_display().all(|p| !p));
+    }
+
+    #[test]
+    fn scroll_right_and_left() {
+        let mut emu = Emu::new();
+        emu.toggle_pixel(5, 3);
+        emu.execute(0x00FB).unwrap();
+        assert_eq!(emu.get_display().filter(|&p| p).count(), 1);
+        assert!(emu.get_pixel(9, 3));
+        emu.execute(0x00FC).unwrap();
+        emu.execute(0x00FC).unwrap();
+        assert_eq!(emu.get_display().filter(|&p| p).count(), 1);
+        assert!(emu.get_pixel(1, 3));
+        // scrolling past the left edge drops the pixel rather than wrapping it
+        emu.execute(0x00FC).unwrap();
+        assert!(emu.get_display().all(|p| !p));
+    }
+
+    #[test]
+    fn scroll_in_hires() {
+        let mut emu = Emu::new();
+        emu.execute(0x00FF).unwrap();
+        emu.toggle_pixel(HIRES_WIDTH - 2, HIRES_HEIGHT - 4);
+        emu.execute(0x00C3).unwrap();
+        assert!(emu.get_pixel(HIRES_WIDTH - 2, HIRES_HEIGHT - 1));
+        emu.execute(0x00FC).unwrap();
+        assert!(emu.get_pixel(HIRES_WIDTH - 6, HIRES_HEIGHT - 1));
+        emu.execute(0x00FB).unwrap();
+        emu.execute(0x00FB).unwrap();
+        assert!(emu.get_display().all(|p| !p));
+    }
+
+    #[test]
+    fn exit_halts_until_reset() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x00, 0xFD, 0x60, 0x01]).unwrap();
+        assert_eq!(emu.tick(), Ok(0x00FD));
+        assert!(emu.is_halted());
+        assert_eq!(emu.tick(), Err(Chip8Error::Halted));
+        assert_eq!(emu.pc, START_ADDR + 2);
+        assert_eq!(emu.v_reg[0], 0);
+        emu.reset();
+        assert!(!emu.is_halted());
+    }
+
+    #[test]
+    fn big_font_address() {
+        let mut emu = Emu::new();
+        emu.v_reg[0] = 0x3;
+        emu.execute(0xF030).unwrap();
+        assert_eq!(emu.i_reg, 110);
+        let i = emu.i_reg as usize;
+        assert_eq!(emu.ram[i..i + 10], BIG_FONTSET[30..40]);
+    }
+
+    #[test]
+    fn draw_16x16_sprite() {
+        let mut emu = Emu::new();
+        emu.execute(0x00FF).unwrap();
+        emu.ram[0x300..0x320].copy_from_slice(&[0xFF; 32]);
+        emu.i_reg = 0x300;
+        emu.v_reg[0] = 10;
+        emu.v_reg[1] = 20;
+        emu.execute(0xD010).unwrap();
+        assert_eq!(emu.get_display().filter(|&p| p).count(), 256);
+        let display: Vec<bool> = emu.get_display().collect();
+        for y in 20..36 {
+            for x in 10..26 {
+                assert!(display[x + HIRES_WIDTH * y]);
+            }
+        }
+        assert_eq!(emu.v_reg[0xF], 0);
+
+        // overlapping by 4 rows collides on exactly those rows
+        emu.v_reg[1] = 32;
+        emu.execute(0xD010).unwrap();
+        assert_eq!(emu.v_reg[0xF], 4);
+
+        // rows clipped off the bottom count too, 12 of them here, and the 4 that are drawn
+        // collide the second time round
+        emu.v_reg[1] = 60;
+        emu.execute(0xD010).unwrap();
+        assert_eq!(emu.v_reg[0xF], 12);
+        assert!(!emu.last_draw_collided());
+        emu.execute(0xD010).unwrap();
+        assert_eq!(emu.v_reg[0xF], 16);
+    }
+
+    #[test]
+    fn draw_8x16_sprite_in_lores() {
+        let mut emu = Emu::new();
+        emu.ram[0x300..0x310].copy_from_slice(&[0xFF; 16]);
+        emu.i_reg = 0x300;
+        emu.execute(0xD000).unwrap();
+        assert_eq!(emu.get_display().filter(|&p| p).count(), 128);
+        assert!(emu.get_pixel(7, 15) && !emu.get_pixel(8, 0) && !emu.get_pixel(0, 16));
+        emu.execute(0xD000).unwrap();
+        assert_eq!(emu.v_reg[0xF], 1);
+
+        // clipped rows don't reach VF in lores
+        emu.v_reg[1] = 24;
+        emu.execute(0xD010).unwrap();
+        assert_eq!(emu.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn rpl_flags_round_trip() {
+        let mut emu = Emu::new();
+        emu.v_reg[..4].copy_from_slice(&[1, 2, 3, 4]);
+        emu.execute(0xF375).unwrap();
+        assert_eq!(emu.rpl_flags(), &[1, 2, 3, 4, 0, 0, 0, 0]);
+        emu.reset();
+        emu.execute(0xF285).unwrap();
+        assert_eq!(emu.v_reg[..4], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn draw_to_each_plane() {
+        let mut emu = Emu::new();
+        // plane 0 gets a full row, plane 1 gets the left half of a row
+        emu.ram[0x300..0x302].copy_from_slice(&[0xFF, 0xF0]);
+        emu.i_reg = 0x300;
+        emu.execute(0xD001).unwrap();
+        emu.execute(0xF201).unwrap();
+        emu.i_reg = 0x301;
+        emu.execute(0xD001).unwrap();
+        assert_eq!(emu.get_color_display()[..9], [3, 3, 3, 3, 1, 1, 1, 1, 0]);
+        let row: Vec<bool> = emu.get_display().take(9).collect();
+        assert_eq!(row, [true, true, true, true, true, true, true, true, false]);
+    }
+
+    #[test]
+    fn draw_to_both_planes() {
+        let mut emu = Emu::new();
+        // with both planes selected the plane 1 sprite follows the plane 0 sprite in RAM
+        emu.ram[0x300..0x302].copy_from_slice(&[0xF0, 0x3C]);
+        emu.i_reg = 0x300;
+        emu.execute(0xF301).unwrap();
+        emu.execute(0xD001).unwrap();
+        assert_eq!(emu.get_color_display()[..8], [1, 1, 3, 3, 2, 2, 0, 0]);
+        assert_eq!(emu.v_reg[0xF], 0);
+        emu.execute(0xD001).unwrap();
+        assert_eq!(emu.v_reg[0xF], 1);
+        assert!(emu.get_color_display().iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn clear_only_selected_planes() {
+        let mut emu = Emu::new();
+        emu.ram[0x300..0x302].copy_from_slice(&[0x80, 0x80]);
+        emu.i_reg = 0x300;
+        emu.execute(0xF301).unwrap();
+        emu.execute(0xD001).unwrap();
+        emu.execute(0xF201).unwrap();
+        emu.execute(0x00E0).unwrap();
+        assert_eq!(emu.get_color_display()[0], 1);
+    }
+
+    #[test]
+    fn register_getters() {
+        let mut emu = Emu::new();
+        // LD V0, 0x05 / LD V1, 0x07 / ADD V0, V1 / LD I, 0x123 / LD DT, V0 / LD ST, V1 / CALL 0x300
+        emu.load_rom(&[
+            0x60, 0x05, 0x61, 0x07, 0x80, 0x14, 0xA1, 0x23, 0xF0, 0x15, 0xF1, 0x18, 0x23, 0x00,
+        ])
+        .unwrap();
+        for _ in 0..7 {
+            emu.tick().unwrap();
+        }
+        assert_eq!(emu.pc(), 0x300);
+        assert_eq!(emu.i_reg(), 0x123);
+        assert_eq!(emu.sp(), 1);
+        assert_eq!(emu.v_reg(0), 0x0C);
+        assert_eq!(emu.v_reg(1), 0x07);
+        assert_eq!(emu.registers()[..3], [0x0C, 0x07, 0x00]);
+        assert_eq!(emu.dt(), 0x0C);
+        assert_eq!(emu.st(), 0x07);
+    }
+
+    #[test]
+    fn memory_inspection() {
+        let emu = Emu::new();
+        assert_eq!(emu.ram().len(), RAM_SIZE);
+        assert_eq!(emu.read_byte(0), Some(FONTSET[0]));
+        assert_eq!(emu.read_byte(RAM_SIZE - 1), Some(0));
+        assert_eq!(emu.read_byte(RAM_SIZE), None);
+    }
+
+    #[test]
+    fn get_and_toggle_pixel() {
+        let mut emu = Emu::new();
+        assert!(!emu.toggle_pixel(3, 4));
+        assert!(emu.get_pixel(3, 4));
+        assert_eq!(emu.planes[0][4], 1 << 3);
+        assert!(emu.toggle_pixel(3, 4));
+        assert!(!emu.get_pixel(3, 4));
+        // outside the lo-res display
+        assert!(!emu.toggle_pixel(SCREEN_WIDTH, 0));
+        assert!(!emu.get_pixel(SCREEN_WIDTH, 0));
+        assert_eq!(emu.planes[0][0], 0);
+    }
+
+    #[test]
+    fn set_pixel_bounds() {
+        let mut emu = Emu::new();
+        emu.set_pixel(63, 31, true);
+        emu.set_pixel(63, 31, true);
+        assert!(emu.get_pixel(63, 31));
+        assert_eq!(emu.take_dirty_rows(), [31]);
+        emu.set_pixel(63, 31, false);
+        assert!(!emu.get_pixel(63, 31));
+
+        // off the lo-res display, even though the hires planes have room
+        emu.set_pixel(SCREEN_WIDTH, 0, true);
+        emu.set_pixel(0, SCREEN_HEIGHT, true);
+        emu.set_pixel(usize::MAX, usize::MAX, true);
+        assert_eq!(emu.planes, [[0; HIRES_HEIGHT]; NUM_PLANES]);
+        assert_eq!(emu.take_dirty_rows(), [31]);
+        assert!(emu.take_dirty_rows().is_empty());
+    }
+
+    #[test]
+    fn draw_wraps_packed_rows_in_hires() {
+        let mut emu = Emu::new();
+        emu.config.clip_sprites = false;
+        emu.execute(0x00FF).unwrap();
+        emu.ram[0x300..0x302].copy_from_slice(&[0xFF, 0xFF]);
+        emu.i_reg = 0x300;
+        emu.v_reg[0] = (HIRES_WIDTH - 4) as u8;
+        emu.execute(0xD011).unwrap();
+        let lit: Vec<usize> = (0..HIRES_WIDTH).filter(|&x| emu.get_pixel(x, 0)).collect();
+        assert_eq!(lit, vec![0, 1, 2, 3, 124, 125, 126, 127]);
+        // collision detection on the packed row
+        emu.execute(0xD011).unwrap();
+        assert_eq!(emu.v_reg[0xF], 1);
+        assert_eq!(emu.planes[0][0], 0);
+    }
+
+    #[test]
+    fn dirty_rows_track_drawing() {
+        let mut emu = Emu::new();
+        assert!(emu.take_dirty_rows().is_empty());
+        // 5 row font glyph at y = 3
+        emu.v_reg[0] = 10;
+        emu.v_reg[1] = 3;
+        emu.execute(0xD015).unwrap();
+        assert_eq!(emu.take_dirty_rows(), vec![3, 4, 5, 6, 7]);
+        assert!(emu.take_dirty_rows().is_empty());
+
+        emu.execute(0x00E0).unwrap();
+        assert_eq!(emu.take_dirty_rows(), (0..SCREEN_HEIGHT).collect::<Vec<_>>());
+        emu.execute(0x00FF).unwrap();
+        emu.execute(0x00FB).unwrap();
+        assert_eq!(emu.take_dirty_rows().len(), HIRES_HEIGHT);
+    }
+
+    #[test]
+    fn xo_chip_sized_ram() {
+        let mut emu = Emu::with_config(Chip8Config { ram_size: 0x10000, ..Chip8Config::default() });
+        assert_eq!(emu.ram().len(), 0x10000);
+        // a ROM that runs past the classic 4KB limit
+        emu.load_rom(&vec![0xAB; 0x2000]).unwrap();
+        assert_eq!(emu.read_byte(0x1000), Some(0xAB));
+        assert_eq!(emu.read_byte(0x21FF), Some(0xAB));
+        assert_eq!(emu.read_byte(0x2200), Some(0));
+        // LD V0, 0x42 beyond 0x1000
+        emu.ram[0x1234..0x1236].copy_from_slice(&[0x60, 0x42]);
+        emu.pc = 0x1234;
+        assert_eq!(emu.tick(), Ok(0x6042));
+        assert_eq!(emu.v_reg[0], 0x42);
+        // the last word of RAM can still be fetched
+        emu.pc = 0xFFFE;
+        assert_eq!(emu.tick(), Ok(0x0000));
+    }
+
+    #[test]
+    fn custom_start_addr() {
+        let mut emu = Emu::with_config(Chip8Config { start_addr: 0x600, ..Chip8Config::default() });
+        assert_eq!(emu.pc(), 0x600);
+        emu.load_rom(&[0x60, 0x07]).unwrap();
+        assert_eq!(emu.read_byte(0x600), Some(0x60));
+        assert_eq!(
+            emu.load_rom(&vec![0; 0xA01]),
+            Err(Chip8Error::RomTooLarge { size: 0xA01, max: 0xA00 })
+        );
+        emu.tick().unwrap();
+        emu.reset();
+        assert_eq!(emu.pc(), 0x600);
+    }
+
+    #[test]
+    fn custom_fontset() {
+        // every glyph is a 1 pixel wide bar at a different column
+        let font: Vec<u8> = (0..16).flat_map(|digit| [0x80 >> (digit % 8); 5]).collect();
+        let mut emu = Emu::with_fontset(&font, 0x100).unwrap();
+        assert_eq!(emu.ram[..FONTSET_SIZE], [0; FONTSET_SIZE]);
+        emu.v_reg[0] = 3;
+        emu.execute(0xF029).unwrap();
+        assert_eq!(emu.i_reg, 0x100 + 15);
+        emu.v_reg[1] = 0;
+        emu.execute(0xD015).unwrap();
+        for row in 0..5 {
+            // VX is 3 so the glyph lands at x = 3, bar in column 3 of the glyph
+            assert_eq!(screen_byte(&emu, 3, row), 0x10);
+        }
+        // reset keeps the custom font in place
+        emu.reset();
+        assert_eq!(emu.ram[0x100..0x100 + font.len()], font[..]);
+        assert_eq!(emu.ram[FONTSET_SIZE..FONTSET_SIZE + BIG_FONTSET_SIZE], BIG_FONTSET);
+    }
+
+    #[test]
+    fn custom_fontset_must_fit() {
+        assert_eq!(
+            Emu::with_fontset(&[0; 80], RAM_SIZE - 79).err(),
+            Some(Chip8Error::FontsetTooLarge { size: 80, max: 79 })
+        );
+        assert!(Emu::with_fontset(&[0; 80], RAM_SIZE - 80).is_ok());
+        assert!(Emu::with_fontset(&[], RAM_SIZE).is_err());
+    }
+
+    #[test]
+    fn same_seed_same_random_numbers() {
+        let mut a = Emu::with_seed(0xC8);
+        let mut b = Emu::with_seed(0xC8);
+        let mut c = Emu::with_seed(0xC9);
+        let mut rolls = Vec::new();
+        for _ in 0..32 {
+            a.execute(0xC0FF).unwrap();
+            b.execute(0xC0FF).unwrap();
+            c.execute(0xC0FF).unwrap();
+            assert_eq!(a.v_reg[0], b.v_reg[0]);
+            rolls.push((a.v_reg[0], c.v_reg[0]));
+        }
+        assert!(rolls.iter().any(|(a, c)| a != c));
+    }
+
+    #[test]
+    fn last_draw_collided_follows_vf() {
+        let mut emu = Emu::new();
+        assert!(!emu.last_draw_collided());
+        emu.execute(0xD005).unwrap();
+        assert!(!emu.last_draw_collided());
+        // overlapping the first glyph turns pixels off
+        emu.execute(0xD005).unwrap();
+        assert!(emu.last_draw_collided());
+        assert_eq!(emu.v_reg[0xF], 1);
+        // drawing onto empty screen clears it again
+        emu.v_reg[0] = 20;
+        emu.execute(0xD005).unwrap();
+        assert!(!emu.last_draw_collided());
+        assert_eq!(emu.v_reg[0xF], 0);
+    }
+
+    // 0x5121 isn't an instruction, 5XY0 needs a 0 in the last nibble
+    const GARBAGE: [u8; 4] = [0x51, 0x21, 0x60, 0x01];
+
+    #[test]
+    fn unknown_opcode_errors_by_default() {
+        let mut emu = Emu::new();
+        emu.load_rom(&GARBAGE).unwrap();
+        assert_eq!(emu.tick(), Err(Chip8Error::UnknownOpcode(0x5121)));
+        assert_eq!(emu.pc, START_ADDR + 2);
+    }
+
+    #[test]
+    fn unknown_opcode_as_nop() {
+        let mut emu = Emu::with_config(Chip8Config {
+            on_unknown_opcode: UnknownOpcode::Nop,
+            ..Chip8Config::default()
+        });
+        emu.load_rom(&GARBAGE).unwrap();
+        assert_eq!(emu.tick(), Ok(0x5121));
+        assert_eq!(emu.tick(), Ok(0x6001));
+        assert_eq!(emu.v_reg[0], 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown opcode 5121")]
+    fn unknown_opcode_panics() {
+        let mut emu = Emu::with_config(Chip8Config {
+            on_unknown_opcode: UnknownOpcode::Panic,
+            ..Chip8Config::default()
+        });
+        emu.load_rom(&GARBAGE).unwrap();
+        let _ = emu.tick();
+    }
+
+    #[test]
+    fn machine_call_per_config() {
+        let mut emu = Emu::new();
+        // SYS 0x123 / LD V0, 1
+        emu.load_rom(&[0x01, 0x23, 0x60, 0x01]).unwrap();
+        assert_eq!(emu.tick(), Err(Chip8Error::UnsupportedMachineCall(0x0123)));
+
+        emu.config.on_machine_call = UnknownOpcode::Nop;
+        emu.pc = START_ADDR;
+        assert_eq!(emu.tick(), Ok(0x0123));
+        assert_eq!(emu.tick(), Ok(0x6001));
+        // unknown opcodes are configured separately
+        emu.config.on_unknown_opcode = UnknownOpcode::Nop;
+        emu.config.on_machine_call = UnknownOpcode::Error;
+        assert_eq!(emu.execute(0x0123), Err(Chip8Error::UnsupportedMachineCall(0x0123)));
+    }
+
+    #[test]
+    fn display_wait_draws_once_per_frame() {
+        let mut emu = Emu::with_config(Chip8Config {
+            display_wait_quirk: true,
+            ..Chip8Config::default()
+        });
+        // LD F, V0 / DRW V0, V0, 5 / ADD V0, 8 / JMP 0x202 - each draw moves the next one down
+        emu.load_rom(&[0xF0, 0x29, 0xD0, 0x05, 0x70, 0x08, 0x12, 0x02]).unwrap();
+
+        // a fresh Emu starts on a frame boundary, but LD F runs after it so the first draw
+        // waits for the next one like it would on the VIP
+        emu.run_frame().unwrap();
+        assert_eq!(emu.v_reg[0], 0);
+        assert_eq!(emu.pc, START_ADDR + 2);
+        assert!(emu.frame_ready());
+        // from then on each frame draws once and the next DXYN waits out the rest of it
+        emu.run_frame().unwrap();
+        emu.run_frame().unwrap();
+        assert_eq!(emu.v_reg[0], 16);
+        // the stalled DXYN still uses up its ticks, 25 of the 30
+        assert_eq!(emu.cycles, 5 + 25 * cycle_cost(0xD005) as u64);
+        assert!(emu.get_pixel(8, 8));
+        assert!(!emu.get_pixel(16, 16));
+
+        // without the quirk the loop goes round several times a frame
+        emu.config.display_wait_quirk = false;
+        emu.run_frame().unwrap();
+        assert!(emu.v_reg[0] > 32);
+    }
+
+    #[test]
+    fn pause_stops_the_cpu() {
+        let mut emu = Emu::new();
+        // ADD V0, 1 / JMP 0x200
+        emu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        emu.dt = 10;
+        emu.pause();
+        assert!(emu.is_paused());
+        assert_eq!(emu.tick(), Ok(0x7001));
+        emu.run_frame().unwrap();
+        assert_eq!(emu.pc, START_ADDR);
+        assert_eq!(emu.v_reg[0], 0);
+        assert_eq!(emu.cycle_count(), 0);
+        assert_eq!(emu.dt, 10);
+
+        // timers can keep running while the CPU is paused
+        emu.config.freeze_timers_on_pause = false;
+        emu.run_frame().unwrap();
+        assert_eq!(emu.pc, START_ADDR);
+        assert_eq!(emu.dt, 9);
+
+        emu.resume();
+        emu.tick().unwrap();
+        assert_eq!(emu.v_reg[0], 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "machine code call 0123")]
+    fn machine_call_panics() {
+        let mut emu = Emu::new();
+        emu.config.on_machine_call = UnknownOpcode::Panic;
+        let _ = emu.execute(0x0123);
+    }
+
+    #[test]
+    fn counts_cycles() {
+        let mut emu = Emu::new();
+        // ADD V0, 1 / JMP 0x200
+        emu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        for _ in 0..25 {
+            emu.tick().unwrap();
+        }
+        assert_eq!(emu.cycle_count(), 25);
+        emu.run_frame().unwrap();
+        assert_eq!(emu.cycle_count(), 25 + emu.config.ticks_per_frame as u64);
+        emu.reset();
+        assert_eq!(emu.cycle_count(), 0);
+    }
+
+    #[test]
+    fn draws_cost_more_cycles() {
+        let mut emu = Emu::new();
+        // LD V0, 5 / DRW V0, V0, 5
+        emu.load_rom(&[0x60, 0x05, 0xD0, 0x05]).unwrap();
+        emu.tick().unwrap();
+        let load = emu.cycle_count();
+        emu.tick().unwrap();
+        assert!(emu.cycle_count() - load > load);
+
+        // step gives the draw more time, 10 cycles fit LD, DRW and then 6 NOPs
+        emu.reset();
+        emu.load_rom(&[0x60, 0x05, 0xD0, 0x05]).unwrap();
+        emu.step(Duration::from_nanos(16_666_667)).unwrap();
+        assert_eq!(emu.cycle_count(), 10);
+        assert_eq!(emu.pc, START_ADDR + 2 * 8);
+    }
+
+    #[test]
+    fn queued_key_events_apply_on_their_cycle() {
+        let mut emu = Emu::new();
+        // ADD V0, 1 / JMP 0x200
+        emu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        emu.queue_key_event(5, 0xA, true);
+        emu.queue_key_event(7, 0xA, false);
+        // out of range keys are dropped like keypress does
+        emu.queue_key_event(5, 0x1F, true);
+
+        emu.tick_n(5).unwrap();
+        assert!(!emu.keys[0xA]);
+        // the sixth instruction starts at cycle 5
+        emu.tick().unwrap();
+        assert!(emu.keys[0xA]);
+        emu.tick_n(2).unwrap();
+        assert!(!emu.keys[0xA]);
+        assert!(emu.key_events.is_empty());
+
+        emu.queue_key_event(100, 0x1, true);
+        emu.clear_key_events();
+        emu.tick_n(100).unwrap();
+        assert!(!emu.keys[0x1]);
+    }
+
+    #[test]
+    fn soft_reset_keeps_program() {
+        let mut emu = Emu::new();
+        // LD V0, 5 / LD I, 0x300 / LD [I], V0 / JMP 0x206
+        let rom = [0x60, 0x05, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06];
+        emu.load_rom(&rom).unwrap();
+        for _ in 0..4 {
+            emu.tick().unwrap();
+        }
+        emu.execute(0xD005).unwrap();
+        emu.st = 4;
+        emu.keypress(2, true);
+
+        emu.soft_reset();
+        assert_eq!(emu.pc, START_ADDR);
+        assert_eq!(emu.v_reg, [0; NUM_REGS]);
+        assert_eq!((emu.i_reg, emu.sp, emu.st), (0, 0, 0));
+        assert!(emu.get_display().all(|p| !p));
+        assert!(!emu.keys[2]);
+        assert_eq!(emu.ram[0x200..0x208], rom);
+        // RAM written by the program survives too
+        assert_eq!(emu.ram[0x300], 5);
+        assert_eq!(emu.tick(), Ok(0x6005));
+
+        emu.reset();
+        assert_eq!(emu.ram[0x200..0x208], [0; 8]);
+    }
+
+    #[test]
+    fn clone_ticks_independently() {
+        let mut emu = Emu::with_seed(7);
+        // ADD V0, 1 / RND V1, 0xFF / JMP 0x200
+        emu.load_rom(&[0x70, 0x01, 0xC1, 0xFF, 0x12, 0x00]).unwrap();
+        emu.tick_n(6).unwrap();
+
+        let mut fork = emu.clone();
+        fork.tick_n(3).unwrap();
+        assert_eq!(fork.v_reg[0], emu.v_reg[0] + 1);
+        assert_eq!(fork.cycle_count(), emu.cycle_count() + 3);
+        // the original carries on from where the fork was taken, rolling the same numbers
+        emu.tick_n(3).unwrap();
+        assert_eq!(emu.v_reg, fork.v_reg);
+        assert_eq!(emu.pc, fork.pc);
+    }
+
+    #[test]
+    fn clone_drops_callbacks() {
+        let mut emu = Emu::new();
+        let traced = Rc::new(RefCell::new(0));
+        let counter = traced.clone();
+        emu.set_trace_hook(Box::new(move |_, _| *counter.borrow_mut() += 1));
+        let mut fork = emu.clone();
+        fork.tick().unwrap();
+        assert_eq!(*traced.borrow(), 0);
+        emu.tick().unwrap();
+        assert_eq!(*traced.borrow(), 1);
+    }
+}
@@ -0,0 +1,100 @@
+// Loading ROMs straight from files and streams, for frontends built with std
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::{Chip8Error, Emu};
+
+// Wraps a load_rom error up for the io::Result loaders
+fn to_io_error(err: Chip8Error) -> io::Error {
+    match err {
+        Chip8Error::RomTooLarge { .. } | Chip8Error::OctoCartridge => {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        }
+        err => io::Error::other(err),
+    }
+}
+
+impl Emu {
+    // Reads the whole file at `path` and loads it with load_rom_auto, so an Octo cartridge is
+    // turned away with a message saying so. A ROM too big for RAM or a cartridge comes back as
+    // an InvalidData error
+    pub fn load_rom_from_path<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let data = fs::read(path)?;
+        self.load_rom_auto(&data).map_err(to_io_error)
+    }
+
+    // Streams a ROM into RAM at the start address. At most one byte more than fits is read, so
+    // an endless or oversized stream fails with InvalidData instead of filling memory
+    pub fn load_rom_from_reader<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        let max = self.ram.len().saturating_sub(self.config.start_addr as usize);
+        let mut data = Vec::new();
+        reader.take(max as u64 + 1).read_to_end(&mut data)?;
+        if data.len() > max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("ROM is more than the {} bytes that fit in RAM", max),
+            ));
+        }
+        self.load_rom(&data).map_err(to_io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Cursor;
+    use std::process;
+
+    #[test]
+    fn load_rom_from_temp_file() {
+        let path = env::temp_dir().join(format!("chip8_core_load_{}.ch8", process::id()));
+        // LD V0, 0x2A / JMP 0x200
+        fs::write(&path, [0x60, 0x2A, 0x12, 0x00]).unwrap();
+        let mut emu = Emu::new();
+        let loaded = emu.load_rom_from_path(&path);
+        fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        assert_eq!(emu.ram()[0x200..0x204], [0x60, 0x2A, 0x12, 0x00]);
+        assert_eq!(emu.tick(), Ok(0x602A));
+        assert_eq!(emu.v_reg(0), 0x2A);
+    }
+
+    #[test]
+    fn load_rom_from_path_errors() {
+        let mut emu = Emu::new();
+        let missing = emu.load_rom_from_path("/definitely/not/a/rom.ch8").unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+
+        let path = env::temp_dir().join(format!("chip8_core_big_{}.ch8", process::id()));
+        fs::write(&path, vec![0; 0x1000]).unwrap();
+        let too_big = emu.load_rom_from_path(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(too_big.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // an empty GIF89a, the smallest Octo cartridge lookalike
+        let path = env::temp_dir().join(format!("chip8_core_cart_{}.gif", process::id()));
+        fs::write(&path, b"GIF89a\x01\0\x01\0\0\0\0\x3B").unwrap();
+        let cart = emu.load_rom_from_path(&path);
+        fs::remove_file(&path).unwrap();
+        let err = cart.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.into_inner().unwrap().to_string(), Chip8Error::OctoCartridge.to_string());
+    }
+
+    #[test]
+    fn load_rom_from_cursor() {
+        let mut emu = Emu::new();
+        emu.load_rom_from_reader(Cursor::new(vec![0xA2, 0x10, 0x00, 0xE0])).unwrap();
+        assert_eq!(emu.ram()[0x200..0x205], [0xA2, 0x10, 0x00, 0xE0, 0x00]);
+    }
+
+    #[test]
+    fn load_rom_from_endless_reader() {
+        let mut emu = Emu::new();
+        let err = emu.load_rom_from_reader(io::repeat(0xFF)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(emu.ram()[0x200], 0x00);
+    }
+}
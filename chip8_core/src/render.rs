@@ -0,0 +1,174 @@
+// Software renderers for frontends and tests that don't want to walk the display themselves
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::Emu;
+
+// Characters render_ascii uses for lit and unlit pixels
+pub const ASCII_ON: char = '█';
+pub const ASCII_OFF: char = ' ';
+
+// RGBA colors by XO-CHIP color index for render_rgba_palette - black when unlit, white for
+// plane 0 like a plain CHIP-8 screen, and greys for plane 1 alone and both planes
+pub const DEFAULT_PALETTE: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x00, 0xFF],
+    [0xFF, 0xFF, 0xFF, 0xFF],
+    [0x55, 0x55, 0x55, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xFF],
+];
+
+impl Emu {
+    // The active display as text, one line per row each ending in '\n', ASCII_ON for lit pixels
+    // and ASCII_OFF for the rest
+    pub fn render_ascii(&self) -> String {
+        self.render_ascii_with(ASCII_ON, ASCII_OFF)
+    }
+
+    // render_ascii with caller-picked characters, e.g. '#' and '.' for readable test output
+    pub fn render_ascii_with(&self, on: char, off: char) -> String {
+        let (width, height) = (self.display_width(), self.display_height());
+        let mut out = String::with_capacity((width + 1) * height);
+        for y in 0..height {
+            out.extend((0..width).map(|x| if self.get_pixel(x, y) { on } else { off }));
+            out.push('\n');
+        }
+        out
+    }
+
+    // The active display as RGBA bytes, row-major and 4 per pixel, ready to upload to a texture.
+    // Only plane 0 is drawn, render_rgba_palette covers XO-CHIP's colors
+    pub fn render_rgba(&self, on: [u8; 4], off: [u8; 4]) -> Vec<u8> {
+        self.get_display().flat_map(|lit| if lit { on } else { off }).collect()
+    }
+
+    // The palette frontends and save_screenshot color the display with, config.palette to
+    // start with
+    pub fn palette(&self) -> [[u8; 4]; 4] {
+        self.config.palette
+    }
+
+    pub fn set_palette(&mut self, palette: [[u8; 4]; 4]) {
+        self.config.palette = palette;
+    }
+
+    // Like render_rgba but colors each pixel by its XO-CHIP plane bits, palette[0] is unlit and
+    // palette[3] is lit on both planes
+    pub fn render_rgba_palette(&self, palette: [[u8; 4]; 4]) -> Vec<u8> {
+        self.get_color_display().iter().flat_map(|&color| palette[color as usize]).collect()
+    }
+
+    // Calls f(row, start_x, length) for every horizontal run of lit plane 0 pixels, top to
+    // bottom and left to right, so run-length renderers can skip the blank parts of the screen
+    pub fn render_spans<F: FnMut(usize, usize, usize)>(&self, mut f: F) {
+        let row_mask = self.row_mask();
+        for y in 0..self.display_height() {
+            let mut row = self.planes[0][y] & row_mask;
+            let mut x = 0;
+            while row != 0 {
+                // hop over the unlit pixels, then measure the lit run that follows
+                let gap = row.trailing_zeros() as usize;
+                row >>= gap;
+                let length = row.trailing_ones() as usize;
+                f(y, x + gap, length);
+                x += gap + length;
+                // a run can fill a whole hires row, which is too far for a plain shift
+                row = row.checked_shr(length as u32).unwrap_or(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    #[test]
+    fn render_font_glyph() {
+        let mut emu = Emu::new();
+        // glyph for 0 at the top left
+        emu.execute(0xD005).unwrap();
+        let text = emu.render_ascii_with('#', '.');
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), SCREEN_HEIGHT);
+        assert!(lines.iter().all(|line| line.len() == SCREEN_WIDTH));
+        let glyph: Vec<&str> = lines[..6].iter().map(|line| &line[..5]).collect();
+        assert_eq!(glyph.join("\n"), "####.\n#..#.\n#..#.\n#..#.\n####.\n.....");
+        assert!(lines[6..].iter().all(|line| !line.contains('#')));
+    }
+
+    #[test]
+    fn render_spans_of_lit_pixels() {
+        let mut emu = Emu::new();
+        // glyph for 0 at the top left, plus a pixel on the right edge
+        emu.execute(0xD005).unwrap();
+        emu.toggle_pixel(SCREEN_WIDTH - 1, 2);
+        let mut spans = Vec::new();
+        emu.render_spans(|row, x, length| spans.push((row, x, length)));
+        let expected = [
+            (0, 0, 4),
+            (1, 0, 1),
+            (1, 3, 1),
+            (2, 0, 1),
+            (2, 3, 1),
+            (2, 63, 1),
+            (3, 0, 1),
+            (3, 3, 1),
+            (4, 0, 4),
+        ];
+        assert_eq!(spans, expected);
+
+        // a completely lit hires row is one span
+        emu.execute(0x00FF).unwrap();
+        for x in 0..HIRES_WIDTH {
+            emu.toggle_pixel(x, HIRES_HEIGHT - 1);
+        }
+        spans.clear();
+        emu.render_spans(|row, x, length| spans.push((row, x, length)));
+        assert_eq!(spans, [(HIRES_HEIGHT - 1, 0, HIRES_WIDTH)]);
+    }
+
+    #[test]
+    fn render_ascii_default_chars() {
+        let mut emu = Emu::new();
+        emu.toggle_pixel(1, 0);
+        let text = emu.render_ascii();
+        assert!(text.starts_with(" █ "));
+        assert_eq!(text.chars().filter(|&c| c == ASCII_ON).count(), 1);
+    }
+
+    const ON: [u8; 4] = [0xFF, 0xB0, 0x00, 0xFF];
+    const OFF: [u8; 4] = [0x10, 0x10, 0x10, 0xFF];
+
+    #[test]
+    fn render_rgba_pixels() {
+        let mut emu = Emu::new();
+        emu.toggle_pixel(2, 1);
+        let rgba = emu.render_rgba(ON, OFF);
+        assert_eq!(rgba.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        let idx = (2 + SCREEN_WIDTH) * 4;
+        assert_eq!(rgba[idx..idx + 4], ON);
+        assert_eq!(rgba[..4], OFF);
+        assert_eq!(rgba.chunks(4).filter(|&px| px == ON).count(), 1);
+
+        emu.execute(0x00FF).unwrap();
+        assert_eq!(emu.render_rgba(ON, OFF).len(), HIRES_WIDTH * HIRES_HEIGHT * 4);
+    }
+
+    #[test]
+    fn render_rgba_palette_colors() {
+        let mut emu = Emu::new();
+        // plane 0 gets a full row, plane 1 gets the left half, as in draw_to_each_plane
+        emu.ram[0x300..0x302].copy_from_slice(&[0xFF, 0xF0]);
+        emu.i_reg = 0x300;
+        emu.execute(0xD001).unwrap();
+        emu.execute(0xF201).unwrap();
+        emu.i_reg = 0x301;
+        emu.execute(0xD001).unwrap();
+        let palette = [[0, 0, 0, 255], [255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        let rgba = emu.render_rgba_palette(palette);
+        assert_eq!(rgba[..4], palette[3]);
+        assert_eq!(rgba[4 * 4..5 * 4], palette[1]);
+        assert_eq!(rgba[8 * 4..9 * 4], palette[0]);
+    }
+}
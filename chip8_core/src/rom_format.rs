@@ -0,0 +1,99 @@
+// Telling ROM files apart before loading them. CHIP-8 programs are almost always distributed
+// as plain binaries (.ch8, .sc8, .xo8) with nothing in front of the first opcode, so the only
+// other format recognized is the Octo cartridge - a GIF with the Octo source code and its
+// options hidden in the image. The source would need Octo's compiler to become a program, so
+// cartridges are detected and turned away instead of being loaded as garbage opcodes. A GIF
+// signature alone could just as well be the first opcodes of a binary (47 49 46 38 is
+// LD V7, 0x49 / CALL 0x638), so a file only counts as a cartridge if it's also laid out like
+// a GIF - a screen descriptor, an optional color table, then a block and a trailing 3B
+use crate::{Chip8Error, Emu};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    // Program bytes as-is, loaded at the start address
+    Binary,
+    // Octo cartridge GIF, starting with a GIF87a or GIF89a signature
+    OctoCartridge,
+}
+
+// GIF signature plus the logical screen descriptor that always follows it
+const GIF_HEADER_LEN: usize = 6 + 7;
+
+// Works out the format of a ROM file from its contents
+pub fn detect_rom_format(data: &[u8]) -> RomFormat {
+    if is_gif(data) {
+        RomFormat::OctoCartridge
+    } else {
+        RomFormat::Binary
+    }
+}
+
+fn is_gif(data: &[u8]) -> bool {
+    if !(data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")) {
+        return false;
+    }
+    if data.len() <= GIF_HEADER_LEN || data.last() != Some(&0x3B) {
+        return false;
+    }
+    // a packed byte with the top bit set means a global color table of 2^(N + 1) RGB entries
+    let packed = data[10];
+    let color_table = if packed & 0x80 != 0 { 3 << ((packed & 0x07) + 1) } else { 0 };
+    // the first block is an extension, an image or the trailer
+    matches!(data.get(GIF_HEADER_LEN + color_table), Some(0x21 | 0x2C | 0x3B))
+}
+
+impl Emu {
+    // Loads a ROM file of any recognized format. Plain binaries go straight to load_rom, an
+    // Octo cartridge fails with Chip8Error::OctoCartridge and leaves RAM untouched
+    pub fn load_rom_auto(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        match detect_rom_format(data) {
+            RomFormat::Binary => self.load_rom(data),
+            RomFormat::OctoCartridge => Err(Chip8Error::OctoCartridge),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_binary_loads() {
+        let mut emu = Emu::new();
+        // LD V0, 0x2A / JMP 0x200
+        let rom = [0x60, 0x2A, 0x12, 0x00];
+        assert_eq!(detect_rom_format(&rom), RomFormat::Binary);
+        emu.load_rom_auto(&rom).unwrap();
+        assert_eq!(emu.ram()[0x200..0x204], rom);
+        let err = emu.load_rom_auto(&[0; 4096]);
+        assert_eq!(err, Err(Chip8Error::RomTooLarge { size: 4096, max: 3584 }));
+    }
+
+    #[test]
+    fn octo_cartridge_is_refused() {
+        let mut emu = Emu::new();
+        // GIF89a, a 160x80 screen with a 2 color global table, then the trailer
+        let mut cart = b"GIF89a".to_vec();
+        cart.extend([0xA0, 0x00, 0x50, 0x00, 0x80, 0x00, 0x00]);
+        cart.extend([0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x3B]);
+        assert_eq!(detect_rom_format(&cart), RomFormat::OctoCartridge);
+        assert_eq!(emu.load_rom_auto(&cart), Err(Chip8Error::OctoCartridge));
+        assert!(emu.ram()[0x200..0x210].iter().all(|&byte| byte == 0));
+        // "GIF" alone isn't enough to count
+        assert_eq!(detect_rom_format(b"GIF"), RomFormat::Binary);
+    }
+
+    #[test]
+    fn binary_starting_with_gif_signature_loads() {
+        let mut emu = Emu::new();
+        // "GIF89a" as opcodes, then JMP 0x200 - no GIF block structure or trailer
+        let rom = [b'G', b'I', b'F', b'8', b'9', b'a', 0x12, 0x00];
+        assert_eq!(detect_rom_format(&rom), RomFormat::Binary);
+        emu.load_rom_auto(&rom).unwrap();
+        assert_eq!(emu.ram()[0x200..0x208], rom);
+        // a trailer byte at the end isn't enough without a block after the color table
+        let mut rom = b"GIF87a".to_vec();
+        rom.extend([0x12, 0x00, 0x12, 0x00, 0x00, 0x00, 0x00, 0x60, 0x3B]);
+        assert_eq!(detect_rom_format(&rom), RomFormat::Binary);
+    }
+}
@@ -0,0 +1,77 @@
+// PNG screenshots of the display, for bug reports and visual regression baselines
+use std::io;
+use std::path::Path;
+
+use image::{ImageBuffer, ImageError, ImageFormat, Rgba};
+
+use crate::Emu;
+
+impl Emu {
+    // Writes the active display to `path` as a PNG with every CHIP-8 pixel drawn as a
+    // `scale` x `scale` block. Pixels are colored by their XO-CHIP plane bits through the
+    // active palette, which is white on black for plain CHIP-8 unless set_palette changed it
+    pub fn save_screenshot<P: AsRef<Path>>(&self, path: P, scale: u32) -> io::Result<()> {
+        if scale == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "scale must be at least 1"));
+        }
+        let (width, height) = self.resolution();
+        let colors = self.get_color_display();
+        let palette = self.palette();
+        let image = ImageBuffer::from_fn(width as u32 * scale, height as u32 * scale, |x, y| {
+            let (x, y) = ((x / scale) as usize, (y / scale) as usize);
+            Rgba(palette[colors[y * width + x] as usize])
+        });
+        image.save_with_format(path, ImageFormat::Png).map_err(|err| match err {
+            ImageError::IoError(err) => err,
+            err => io::Error::other(err),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_PALETTE;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn screenshot_is_scaled() {
+        let mut emu = Emu::new();
+        // LD F, V0 / DRW V0, V0, 5 - the 0 glyph in the top left
+        emu.load_rom(&[0xF0, 0x29, 0xD0, 0x05]).unwrap();
+        emu.tick_n(2).unwrap();
+
+        let path = env::temp_dir().join(format!("chip8_core_screenshot_{}.png", process::id()));
+        emu.save_screenshot(&path, 4).unwrap();
+        let image = image::open(&path);
+        fs::remove_file(&path).unwrap();
+        let image = image.unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (64 * 4, 32 * 4));
+        assert_eq!(image.get_pixel(3, 3).0, DEFAULT_PALETTE[1]);
+        assert_eq!(image.get_pixel(4 * 4, 0).0, DEFAULT_PALETTE[0]);
+
+        assert_eq!(
+            emu.save_screenshot(&path, 0).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn screenshot_uses_active_palette() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0xF0, 0x29, 0xD0, 0x05]).unwrap();
+        emu.tick_n(2).unwrap();
+        let green = [[0x00, 0x20, 0x00, 0xFF], [0x33, 0xFF, 0x33, 0xFF], [0; 4], [0; 4]];
+        emu.set_palette(green);
+
+        let path = env::temp_dir().join(format!("chip8_core_palette_{}.png", process::id()));
+        emu.save_screenshot(&path, 1).unwrap();
+        let image = image::open(&path);
+        fs::remove_file(&path).unwrap();
+        let image = image.unwrap().to_rgba8();
+        assert_eq!(image.get_pixel(0, 0).0, green[1]);
+        assert_eq!(image.get_pixel(8, 0).0, green[0]);
+    }
+}
@@ -0,0 +1,242 @@
+// serde support for save states. RAM and the display planes are too big for serde's built-in
+// array impls, so Emu is mirrored into an owned struct with those fields as Vecs and the lengths
+// are checked again on the way back in
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    default_rng, Chip8Config, Emu, Rng, XorShiftRng, HIRES_HEIGHT, MAX_RAM_SIZE, MIN_RAM_SIZE,
+    NUM_KEYS, NUM_PLANES, NUM_REGS, NUM_RPL_FLAGS, STACK_SIZE,
+};
+
+#[derive(Serialize, Deserialize)]
+struct EmuState {
+    config: Chip8Config,
+    // the small font and where FX29 finds it, so with_fontset sessions keep their font
+    fontset: Vec<u8>,
+    font_addr: u16,
+    pc: u16,
+    ram: Vec<u8>,
+    planes: Vec<Vec<u128>>,
+    plane_mask: u8,
+    hires: bool,
+    v_reg: [u8; NUM_REGS],
+    i_reg: u16,
+    sp: u16,
+    stack: [u16; STACK_SIZE],
+    keys: [bool; NUM_KEYS],
+    dt: u8,
+    st: u8,
+    rpl_flags: [u8; NUM_RPL_FLAGS],
+    waiting_for_key: bool,
+    halted: bool,
+    frame_ready: bool,
+    paused: bool,
+    last_draw_collided: bool,
+    sound_on: bool,
+    // queued (cycle, key, pressed) events in the order tick applies them
+    key_events: Vec<(u64, u8, bool)>,
+    cycles: u64,
+    cpu_accum: u128,
+    timer_accum: u128,
+    // None when the RNG can't be captured, like save_state
+    rng_state: Option<u64>,
+}
+
+impl Serialize for Emu {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EmuState {
+            config: self.config,
+            fontset: self.fontset.clone(),
+            font_addr: self.font_addr,
+            pc: self.pc,
+            ram: self.ram.to_vec(),
+            planes: self.planes.iter().map(|plane| plane.to_vec()).collect(),
+            plane_mask: self.plane_mask,
+            hires: self.hires,
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            sp: self.sp,
+            stack: self.stack,
+            keys: self.keys,
+            dt: self.dt,
+            st: self.st,
+            rpl_flags: self.rpl_flags,
+            waiting_for_key: self.waiting_for_key,
+            halted: self.halted,
+            frame_ready: self.frame_ready,
+            paused: self.paused,
+            last_draw_collided: self.last_draw_collided,
+            sound_on: self.sound_on,
+            key_events: self
+                .key_events
+                .iter()
+                .flat_map(|(&cycle, events)| {
+                    events.iter().map(move |&(key, pressed)| (cycle, key, pressed))
+                })
+                .collect(),
+            cycles: self.cycles,
+            cpu_accum: self.cpu_accum,
+            timer_accum: self.timer_accum,
+            rng_state: self.rng.state(),
+        }
+        .serialize(serializer)
+    }
+}
+
+// The machine state comes back along with the config and font it was saved with. Hooks,
+// breakpoints and the rewind buffer aren't saved. A saved RNG state continues in the xorshift
+// generator with_seed uses, otherwise the default RNG is used
+impl<'de> Deserialize<'de> for Emu {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = EmuState::deserialize(deserializer)?;
+        if !(MIN_RAM_SIZE..=MAX_RAM_SIZE).contains(&state.ram.len()) {
+            return Err(D::Error::invalid_length(state.ram.len(), &"240 to 65536 bytes of RAM"));
+        }
+        if state.planes.len() != NUM_PLANES {
+            return Err(D::Error::invalid_length(state.planes.len(), &"2 display planes"));
+        }
+        if let Some(plane) = state.planes.iter().find(|plane| plane.len() != HIRES_HEIGHT) {
+            return Err(D::Error::invalid_length(plane.len(), &"64 rows per plane"));
+        }
+        if state.sp as usize > STACK_SIZE {
+            return Err(D::Error::custom("stack pointer past the end of the stack"));
+        }
+        if state.config.ram_size != state.ram.len() {
+            return Err(D::Error::custom("ram_size doesn't match the saved RAM"));
+        }
+        if state.font_addr as usize + state.fontset.len() > state.ram.len() {
+            return Err(D::Error::custom("font runs past the end of RAM"));
+        }
+
+        let rng = match state.rng_state {
+            Some(rng_state) => Box::new(XorShiftRng::new(rng_state)) as Box<dyn Rng>,
+            None => default_rng(),
+        };
+        let mut emu = Emu::build(state.config, rng);
+        emu.fontset = state.fontset;
+        emu.font_addr = state.font_addr;
+        emu.pc = state.pc;
+        emu.ram.copy_from_slice(&state.ram);
+        for (plane, saved) in emu.planes.iter_mut().zip(&state.planes) {
+            plane.copy_from_slice(saved);
+        }
+        emu.plane_mask = state.plane_mask;
+        emu.hires = state.hires;
+        emu.v_reg = state.v_reg;
+        emu.i_reg = state.i_reg;
+        emu.sp = state.sp;
+        emu.stack = state.stack;
+        emu.keys = state.keys;
+        emu.dt = state.dt;
+        emu.st = state.st;
+        emu.rpl_flags = state.rpl_flags;
+        emu.waiting_for_key = state.waiting_for_key;
+        emu.halted = state.halted;
+        emu.frame_ready = state.frame_ready;
+        emu.paused = state.paused;
+        emu.last_draw_collided = state.last_draw_collided;
+        emu.sound_on = state.sound_on;
+        for (cycle, key, pressed) in state.key_events {
+            emu.queue_key_event(cycle, key, pressed);
+        }
+        emu.cycles = state.cycles;
+        emu.cpu_accum = state.cpu_accum;
+        emu.timer_accum = state.timer_accum;
+        Ok(emu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut emu = Emu::new();
+        // LD V0, 0x0A / LD F, V0 / DRW V0, V0, 5 / CALL 0x300
+        emu.load_rom(&[0x60, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0x23, 0x00]).unwrap();
+        for _ in 0..4 {
+            emu.tick().unwrap();
+        }
+        emu.keypress(3, true);
+        emu.dt = 7;
+        emu.st = 9;
+
+        let json = serde_json::to_string(&emu).unwrap();
+        let restored: Emu = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.pc, emu.pc);
+        assert_eq!(restored.ram, emu.ram);
+        assert_eq!(restored.planes, emu.planes);
+        assert_eq!(restored.v_reg, emu.v_reg);
+        assert_eq!(restored.i_reg, emu.i_reg);
+        assert_eq!(restored.sp, emu.sp);
+        assert_eq!(restored.stack, emu.stack);
+        assert_eq!(restored.keys, emu.keys);
+        assert_eq!(restored.dt, emu.dt);
+        assert_eq!(restored.st, emu.st);
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    }
+
+    #[test]
+    fn round_trip_is_identical() {
+        let mut emu = Emu::with_seed(5);
+        // RND V0, 0xFF / DRW V0, V0, 5 / JMP 0x200
+        emu.load_rom(&[0xC0, 0xFF, 0xD0, 0x05, 0x12, 0x00]).unwrap();
+        emu.queue_key_event(30, 0x4, true);
+        emu.queue_key_event(30, 0x5, true);
+        emu.st = 2;
+        emu.step(core::time::Duration::from_millis(20)).unwrap();
+        emu.pause();
+
+        let restored: Emu = serde_json::from_value(serde_json::to_value(&emu).unwrap()).unwrap();
+        // the binary save state covers every field, so matching blobs means nothing was lost
+        assert_eq!(restored.save_state(), emu.save_state());
+        assert!(restored.is_paused());
+    }
+
+    #[test]
+    fn round_trip_keeps_config_and_font() {
+        let config = Chip8Config { palette: [[1, 2, 3, 4]; 4], ..Chip8Config::schip() };
+        let font: Vec<u8> = (0..80).collect();
+        let mut emu = Emu::with_fontset(&font, 0x100).unwrap();
+        emu.config = config;
+        // LD V0, 2 / LD F, V0
+        emu.load_rom(&[0x60, 0x02, 0xF0, 0x29]).unwrap();
+
+        let mut restored: Emu =
+            serde_json::from_value(serde_json::to_value(&emu).unwrap()).unwrap();
+        assert_eq!(restored.config, config);
+        restored.tick_n(2).unwrap();
+        assert_eq!(restored.i_reg(), 0x100 + 2 * 5);
+        // a reset puts the custom font back, not the built-in one
+        restored.reset();
+        assert_eq!(restored.ram()[0x100..0x150], font[..]);
+    }
+
+    #[test]
+    fn rejects_mismatched_ram_size() {
+        let emu = Emu::new();
+        let mut value = serde_json::to_value(&emu).unwrap();
+        value["config"]["ram_size"] = serde_json::json!(MAX_RAM_SIZE);
+        assert!(serde_json::from_value::<Emu>(value).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_ram() {
+        let emu = Emu::new();
+        let mut value = serde_json::to_value(&emu).unwrap();
+        value["ram"].as_array_mut().unwrap().truncate(MIN_RAM_SIZE - 1);
+        assert!(serde_json::from_value::<Emu>(value).is_err());
+    }
+
+    #[test]
+    fn round_trip_keeps_ram_size() {
+        let config = Chip8Config { ram_size: MAX_RAM_SIZE, ..Chip8Config::default() };
+        let emu = Emu::with_config(config);
+        let restored: Emu = serde_json::from_value(serde_json::to_value(&emu).unwrap()).unwrap();
+        assert_eq!(restored.ram.len(), MAX_RAM_SIZE);
+    }
+}
@@ -0,0 +1,455 @@
+// Compact binary save states, for frontends that want in-memory save slots without serde.
+//
+// Layout (multi-byte values are big-endian):
+//   "C8ST"  magic
+//   u8      format version
+//   u16     pc, i, sp
+//   u8      dt, st
+//   u8      hires, plane mask, halted, waiting for key
+//   u8      frame ready, paused, last draw collided, sound on
+//   [u8]    V0 - VF, RPL flags
+//   [u16]   stack
+//   u16     keys, bit N set when key N is pressed
+//   u32     number of queued key events, then u64 cycle, u8 key, u8 pressed for each
+//   u64     cycle count
+//   u128    step's CPU and timer time accumulators
+//   [u8]    RAM, all config.ram_size bytes so blobs only load into the same RAM size
+//   u8      1 if the RNG state follows, 0 if the RNG can't be captured
+//   u64     RNG state, only present after a 1
+//   [u128]  each display plane, one word per row with pixel x in bit x
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{
+    Chip8Error, Emu, HIRES_HEIGHT, NUM_KEYS, NUM_PLANES, NUM_REGS, NUM_RPL_FLAGS, STACK_SIZE,
+};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 4;
+const ROW_BYTES: usize = 16;
+
+// Pulls fixed-size fields off the front of a save state
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.bytes.len() < len {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Chip8Error> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, Chip8Error> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn u128(&mut self) -> Result<u128, Chip8Error> {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(self.take(16)?);
+        Ok(u128::from_be_bytes(bytes))
+    }
+
+    fn bool(&mut self) -> Result<bool, Chip8Error> {
+        Ok(self.u8()? != 0)
+    }
+}
+
+impl Emu {
+    // Packs the full machine state into a versioned binary blob for load_state
+    pub fn save_state(&self) -> Vec<u8> {
+        let planes_len = NUM_PLANES * HIRES_HEIGHT * ROW_BYTES;
+        let mut out = Vec::with_capacity(64 + self.ram.len() + planes_len);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.pc.to_be_bytes());
+        out.extend_from_slice(&self.i_reg.to_be_bytes());
+        out.extend_from_slice(&self.sp.to_be_bytes());
+        out.push(self.dt);
+        out.push(self.st);
+        out.push(self.hires as u8);
+        out.push(self.plane_mask);
+        out.push(self.halted as u8);
+        out.push(self.waiting_for_key as u8);
+        out.push(self.frame_ready as u8);
+        out.push(self.paused as u8);
+        out.push(self.last_draw_collided as u8);
+        out.push(self.sound_on as u8);
+        out.extend_from_slice(&self.v_reg);
+        out.extend_from_slice(&self.rpl_flags);
+        for addr in self.stack {
+            out.extend_from_slice(&addr.to_be_bytes());
+        }
+        let keys = (0..NUM_KEYS).fold(0u16, |bits, key| bits | (self.keys[key] as u16) << key);
+        out.extend_from_slice(&keys.to_be_bytes());
+        let key_events: Vec<_> = self
+            .key_events
+            .iter()
+            .flat_map(|(&cycle, events)| events.iter().map(move |&event| (cycle, event)))
+            .collect();
+        out.extend_from_slice(&(key_events.len() as u32).to_be_bytes());
+        for (cycle, (key, pressed)) in key_events {
+            out.extend_from_slice(&cycle.to_be_bytes());
+            out.push(key);
+            out.push(pressed as u8);
+        }
+        out.extend_from_slice(&self.cycles.to_be_bytes());
+        out.extend_from_slice(&self.cpu_accum.to_be_bytes());
+        out.extend_from_slice(&self.timer_accum.to_be_bytes());
+        out.extend_from_slice(&self.ram);
+        match self.rng.state() {
+            Some(state) => {
+                out.push(1);
+                out.extend_from_slice(&state.to_be_bytes());
+            }
+            None => out.push(0),
+        }
+        for row in self.planes.iter().flatten() {
+            out.extend_from_slice(&row.to_be_bytes());
+        }
+        out
+    }
+
+    // Restores a blob from save_state. Nothing is changed unless the whole blob is valid
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), Chip8Error> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC || reader.u8()? != VERSION {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+        let pc = reader.u16()?;
+        let i_reg = reader.u16()?;
+        let sp = reader.u16()?;
+        let dt = reader.u8()?;
+        let st = reader.u8()?;
+        let hires = reader.bool()?;
+        let plane_mask = reader.u8()?;
+        let halted = reader.bool()?;
+        let waiting_for_key = reader.bool()?;
+        let frame_ready = reader.bool()?;
+        let paused = reader.bool()?;
+        let last_draw_collided = reader.bool()?;
+        let sound_on = reader.bool()?;
+        let mut v_reg = [0; NUM_REGS];
+        v_reg.copy_from_slice(reader.take(NUM_REGS)?);
+        let mut rpl_flags = [0; NUM_RPL_FLAGS];
+        rpl_flags.copy_from_slice(reader.take(NUM_RPL_FLAGS)?);
+        let mut stack = [0; STACK_SIZE];
+        for addr in stack.iter_mut() {
+            *addr = reader.u16()?;
+        }
+        let key_bits = reader.u16()?;
+        let mut key_events: BTreeMap<u64, Vec<(u8, bool)>> = BTreeMap::new();
+        for _ in 0..reader.u32()? {
+            let cycle = reader.u64()?;
+            let key = reader.u8()?;
+            let pressed = reader.bool()?;
+            key_events.entry(cycle).or_default().push((key, pressed));
+        }
+        let cycles = reader.u64()?;
+        let cpu_accum = reader.u128()?;
+        let timer_accum = reader.u128()?;
+        let ram = reader.take(self.ram.len())?;
+        let rng_state = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.u64()?),
+            _ => return Err(Chip8Error::InvalidSaveState),
+        };
+        let planes = reader.take(NUM_PLANES * HIRES_HEIGHT * ROW_BYTES)?;
+        if !reader.bytes.is_empty() || sp as usize > STACK_SIZE {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+
+        self.pc = pc;
+        self.i_reg = i_reg;
+        self.sp = sp;
+        self.dt = dt;
+        self.st = st;
+        self.hires = hires;
+        self.plane_mask = plane_mask;
+        self.halted = halted;
+        self.waiting_for_key = waiting_for_key;
+        self.frame_ready = frame_ready;
+        self.paused = paused;
+        self.last_draw_collided = last_draw_collided;
+        self.sound_on = sound_on;
+        self.v_reg = v_reg;
+        self.rpl_flags = rpl_flags;
+        self.stack = stack;
+        for (key, pressed) in self.keys.iter_mut().enumerate() {
+            *pressed = key_bits & (1 << key) != 0;
+        }
+        self.key_events = key_events;
+        self.cycles = cycles;
+        self.cpu_accum = cpu_accum;
+        self.timer_accum = timer_accum;
+        self.ram.copy_from_slice(ram);
+        // an RNG that can't take the saved state just carries on from where it is
+        if let Some(state) = rng_state {
+            self.rng.set_state(state);
+        }
+        for (row, bytes) in self.planes.iter_mut().flatten().zip(planes.chunks(ROW_BYTES)) {
+            let mut word = [0; ROW_BYTES];
+            word.copy_from_slice(bytes);
+            *row = u128::from_be_bytes(word);
+        }
+        self.dirty_rows = u64::MAX;
+        Ok(())
+    }
+
+    // Sets the core machine state in one go, for importing a dump taken by another emulator.
+    // The dump has to cover all of RAM, with sp no deeper than the stack and pc inside RAM,
+    // otherwise nothing changes. The display, keys and config are left as they are
+    #[allow(clippy::too_many_arguments)]
+    pub fn restore(
+        &mut self,
+        ram: &[u8],
+        v_reg: [u8; NUM_REGS],
+        i: u16,
+        pc: u16,
+        sp: u16,
+        stack: [u16; STACK_SIZE],
+        dt: u8,
+        st: u8,
+    ) -> Result<(), Chip8Error> {
+        if ram.len() != self.ram.len() || sp as usize > STACK_SIZE || pc as usize >= ram.len() {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+        self.ram.copy_from_slice(ram);
+        self.v_reg = v_reg;
+        self.i_reg = i;
+        self.pc = pc;
+        self.sp = sp;
+        self.stack = stack;
+        self.dt = dt;
+        self.st = st;
+        Ok(())
+    }
+
+    // Keeps the last n frames for rewind, 0 turns rewinding off and drops any saved frames
+    pub fn set_rewind_depth(&mut self, n: usize) {
+        self.rewind_depth = n;
+        while self.rewind_buffer.len() > n {
+            self.rewind_buffer.pop_front();
+        }
+    }
+
+    // Restores the state from the start of the most recent frame, returns false once there
+    // is nothing left to rewind
+    pub fn rewind(&mut self) -> bool {
+        match self.rewind_buffer.pop_back() {
+            Some(state) => self.load_state(&state).is_ok(),
+            None => false,
+        }
+    }
+
+    pub(crate) fn push_rewind_state(&mut self) {
+        if self.rewind_depth == 0 {
+            return;
+        }
+        if self.rewind_buffer.len() == self.rewind_depth {
+            self.rewind_buffer.pop_front();
+        }
+        let state = self.save_state();
+        self.rewind_buffer.push_back(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8Config;
+    use core::time::Duration;
+
+    #[test]
+    fn save_and_load_state() {
+        let mut emu = Emu::new();
+        // LD V0, 0x0A / LD F, V0 / DRW V0, V0, 5 / CALL 0x300
+        emu.load_rom(&[0x60, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0x23, 0x00]).unwrap();
+        for _ in 0..4 {
+            emu.tick().unwrap();
+        }
+        emu.keypress(0xC, true);
+        emu.dt = 30;
+        let saved = emu.save_state();
+        let (pc, ram, planes, v_reg) = (emu.pc, emu.ram.clone(), emu.planes, emu.v_reg);
+
+        emu.reset();
+        emu.load_rom(&[0xFF; 16]).unwrap();
+        emu.v_reg[3] = 0x33;
+        emu.load_state(&saved).unwrap();
+
+        assert_eq!(emu.pc, pc);
+        assert_eq!(emu.ram, ram);
+        assert_eq!(emu.planes, planes);
+        assert_eq!(emu.v_reg, v_reg);
+        assert_eq!(emu.i_reg, 50);
+        assert_eq!(emu.sp, 1);
+        assert_eq!(emu.stack[0], 0x208);
+        assert!(emu.keys[0xC]);
+        assert_eq!(emu.keys.iter().filter(|&&k| k).count(), 1);
+        assert_eq!(emu.dt, 30);
+        assert_eq!(emu.save_state(), saved);
+    }
+
+    #[test]
+    fn load_state_rejects_bad_blobs() {
+        let mut emu = Emu::new();
+        let saved = emu.save_state();
+
+        assert_eq!(emu.load_state(&[]), Err(Chip8Error::InvalidSaveState));
+        assert_eq!(emu.load_state(&saved[..saved.len() - 1]), Err(Chip8Error::InvalidSaveState));
+
+        let mut bad_magic = saved.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(emu.load_state(&bad_magic), Err(Chip8Error::InvalidSaveState));
+
+        let mut bad_version = saved.clone();
+        bad_version[4] = VERSION + 1;
+        assert_eq!(emu.load_state(&bad_version), Err(Chip8Error::InvalidSaveState));
+
+        let mut trailing = saved.clone();
+        trailing.push(0);
+        assert_eq!(emu.load_state(&trailing), Err(Chip8Error::InvalidSaveState));
+    }
+
+    #[test]
+    fn restore_sets_every_field() {
+        let mut emu = Emu::new();
+        let mut ram = vec![0; 4096];
+        ram[0x300..0x304].copy_from_slice(&[0x60, 0x2A, 0x00, 0xEE]);
+        let v_reg: [u8; NUM_REGS] = core::array::from_fn(|idx| idx as u8 * 3);
+        let mut stack = [0; STACK_SIZE];
+        stack[0] = 0x204;
+        emu.restore(&ram, v_reg, 0x123, 0x300, 1, stack, 40, 5).unwrap();
+
+        assert_eq!(emu.ram(), &ram[..]);
+        assert_eq!(emu.registers(), &v_reg);
+        assert_eq!(emu.i_reg(), 0x123);
+        assert_eq!(emu.pc(), 0x300);
+        assert_eq!(emu.call_stack(), [0x204]);
+        assert_eq!((emu.dt(), emu.st()), (40, 5));
+        // and it runs from there
+        emu.tick_n(2).unwrap();
+        assert_eq!((emu.v_reg(0), emu.pc()), (0x2A, 0x204));
+    }
+
+    #[test]
+    fn restore_rejects_bad_dumps() {
+        let mut emu = Emu::new();
+        let stack = [0; STACK_SIZE];
+        let ram = [0xAA; 4096];
+        let regs = [0; NUM_REGS];
+        let bad = Err(Chip8Error::InvalidSaveState);
+        assert_eq!(emu.restore(&ram[..4095], regs, 0, 0x200, 0, stack, 0, 0), bad);
+        assert_eq!(emu.restore(&ram, regs, 0, 0x200, 17, stack, 0, 0), bad);
+        assert_eq!(emu.restore(&ram, regs, 0, 0x1000, 0, stack, 0, 0), bad);
+        // nothing was applied
+        assert_eq!(emu.read_byte(0x200), Some(0));
+    }
+
+    #[test]
+    fn rewind_frames() {
+        let mut emu =
+            Emu::with_config(Chip8Config { ticks_per_frame: 1, ..Chip8Config::default() });
+        // ADD V0, 1 in a loop
+        emu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        emu.set_rewind_depth(3);
+        let mut frames = Vec::new();
+        for _ in 0..6 {
+            frames.push((emu.pc, emu.v_reg));
+            emu.run_frame().unwrap();
+        }
+
+        assert!(emu.rewind());
+        assert_eq!((emu.pc, emu.v_reg), frames[5]);
+        assert!(emu.rewind());
+        assert!(emu.rewind());
+        assert_eq!((emu.pc, emu.v_reg), frames[3]);
+        // only 3 frames were kept
+        assert!(!emu.rewind());
+        assert_eq!((emu.pc, emu.v_reg), frames[3]);
+    }
+
+    #[test]
+    fn rewind_disabled_by_default() {
+        let mut emu = Emu::new();
+        emu.run_frame().unwrap();
+        assert!(!emu.rewind());
+
+        emu.set_rewind_depth(2);
+        emu.run_frame().unwrap();
+        emu.set_rewind_depth(0);
+        assert!(!emu.rewind());
+    }
+
+    #[test]
+    fn save_state_restores_seeded_rng() {
+        let mut emu = Emu::with_seed(42);
+        emu.execute(0xC0FF).unwrap();
+        let saved = emu.save_state();
+        let mut rolls = Vec::new();
+        for _ in 0..4 {
+            emu.execute(0xC0FF).unwrap();
+            rolls.push(emu.v_reg[0]);
+        }
+
+        emu.load_state(&saved).unwrap();
+        for &roll in &rolls {
+            emu.execute(0xC0FF).unwrap();
+            assert_eq!(emu.v_reg[0], roll);
+        }
+        // the rand-backed default RNG has no state to save
+        #[cfg(feature = "std")]
+        assert_eq!(Emu::new().save_state().len(), saved.len() - 8);
+    }
+
+    #[test]
+    fn load_state_mid_frame_replays_identically() {
+        let mut emu = Emu::with_seed(9);
+        // RND V0, 0xFF / SKP V1 / ADD V2, 1 / DRW V0, V2, 5 / JMP 0x200
+        emu.load_rom(&[0xC0, 0xFF, 0xE1, 0x9E, 0x72, 0x01, 0xD0, 0x25, 0x12, 0x00]).unwrap();
+        emu.queue_key_event(40, 0x0, true);
+        emu.queue_key_event(90, 0x0, false);
+        emu.dt = 50;
+        emu.st = 3;
+        let slice = Duration::from_millis(7);
+        let run = |emu: &mut Emu| {
+            (0..20)
+                .map(|_| {
+                    emu.step(slice).unwrap();
+                    let display: Vec<bool> = emu.get_display().collect();
+                    (emu.dump_state(), emu.cycle_count(), display, emu.keys)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // 21ms in, partway through the second frame with key events still queued
+        for _ in 0..3 {
+            emu.step(slice).unwrap();
+        }
+        let saved = emu.save_state();
+        let first = run(&mut emu);
+        emu.load_state(&saved).unwrap();
+        assert_eq!(run(&mut emu), first);
+    }
+}
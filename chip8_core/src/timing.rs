@@ -0,0 +1,42 @@
+// How long each instruction takes, in cycles where a plain register instruction is 1. These
+// weights are made up, not measured. They only follow the shape of the COSMAC VIP interpreter,
+// whose slow instructions are the ones that loop over the screen or over memory - clearing and
+// scrolling the display, drawing a sprite row by row, BCD's repeated subtraction and FX55/FX65
+// copying a register at a time. The real VIP timings are far larger and vary with the operands.
+// The weights are kept small so ticks_per_frame still reads as roughly instructions per frame
+use crate::decode::{decode, Instruction};
+
+pub fn cycle_cost(op: u16) -> u32 {
+    use Instruction::*;
+    match decode(op) {
+        Cls | ScrollDown { .. } | ScrollRight | ScrollLeft => 4,
+        // one cycle to set up and one for every two sprite rows, DXY0 draws 16
+        Draw { n, .. } => {
+            let rows = if n == 0 { 16 } else { n as u32 };
+            1 + rows / 2
+        }
+        Bcd { .. } => 3,
+        // one for every four registers copied
+        Store { x } | Load { x } | StoreFlags { x } | LoadFlags { x } => 1 + (x as u32 + 1) / 4,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_instructions_cost_more() {
+        // LD V0, 5 / ADD V0, V1 / JMP 0x200
+        assert_eq!([0x6005, 0x8014, 0x1200].map(cycle_cost), [1, 1, 1]);
+        assert_eq!(cycle_cost(0x00E0), 4);
+        assert_eq!(cycle_cost(0xD015), 3);
+        assert_eq!(cycle_cost(0xD010), 9);
+        assert_eq!(cycle_cost(0xF033), 3);
+        assert_eq!(cycle_cost(0xF055), 1);
+        assert_eq!(cycle_cost(0xFF65), 5);
+        // unknown words cost the same as any other instruction
+        assert_eq!(cycle_cost(0xFFFF), 1);
+    }
+}
@@ -0,0 +1,111 @@
+// Prints a disassembly listing of a ROM, one `address: opcode  mnemonic` line per word
+//
+//   chip8_dasm [--start ADDR] [--length BYTES] [--raw] path/to/game
+//
+// The ROM is loaded at 0x200 like the emulator does, so addresses match what a debugger shows.
+// --start and --length pick a range of RAM (decimal or 0x hex) and default to the whole ROM.
+// --raw adds each word's bits as pixels, for spotting sprite data among the code
+use std::env;
+use std::fs;
+use std::process;
+
+use chip8_core::Emu;
+
+const USAGE: &str = "[--start ADDR] [--length BYTES] [--raw] path/to/game";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    start: Option<usize>,
+    length: Option<usize>,
+    raw: bool,
+    rom: String,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            eprintln!("Usage: {} {}", args[0], USAGE);
+            process::exit(1);
+        }
+    };
+
+    let rom = match fs::read(&options.rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Couldn't read {}: {}", options.rom, err);
+            process::exit(1);
+        }
+    };
+    let mut emu = Emu::new();
+    if let Err(err) = emu.load_rom(&rom) {
+        eprintln!("Couldn't load {}: {}", options.rom, err);
+        process::exit(1);
+    }
+
+    let start = options.start.unwrap_or(emu.pc() as usize);
+    let length = options.length.unwrap_or(rom.len());
+    for (addr, op, text) in emu.disassemble_range(start, length) {
+        if options.raw {
+            println!("{:03X}: {:04X}  {:<18} {}", addr, op, text, pixels(op));
+        } else {
+            println!("{:03X}: {:04X}  {}", addr, op, text);
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options::default();
+    let mut rom = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--start" => options.start = Some(parse_number(args.next()?)?),
+            "--length" => options.length = Some(parse_number(args.next()?)?),
+            "--raw" => options.raw = true,
+            path if rom.is_none() && !path.starts_with("--") => rom = Some(path.to_string()),
+            _ => return None,
+        }
+    }
+    options.rom = rom?;
+    Some(options)
+}
+
+fn parse_number(arg: &str) -> Option<usize> {
+    match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => arg.parse().ok(),
+    }
+}
+
+// The word's 16 bits as a sprite row, # for set and . for clear
+fn pixels(op: u16) -> String {
+    (0..16).rev().map(|bit| if op & (1 << bit) != 0 { '#' } else { '.' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse_args(&args(&["--start", "0x202", "--length", "4", "--raw", "a.ch8"]));
+        assert_eq!(
+            options,
+            Some(Options { start: Some(0x202), length: Some(4), raw: true, rom: "a.ch8".into() })
+        );
+        assert_eq!(parse_args(&args(&["a.ch8", "b.ch8"])), None);
+        assert_eq!(parse_args(&args(&["--start", "zz", "a.ch8"])), None);
+        assert_eq!(parse_args(&args(&["--raw"])), None);
+    }
+
+    #[test]
+    fn pixel_column() {
+        assert_eq!(pixels(0xF090), "####....#..#....");
+    }
+}
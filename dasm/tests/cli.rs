@@ -0,0 +1,45 @@
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+// Writes `rom` to a temp file, runs chip8_dasm on it with `args` and returns stdout
+fn run_dasm(name: &str, rom: &[u8], args: &[&str]) -> String {
+    let path = env::temp_dir().join(format!("chip8_dasm_{}_{}.ch8", name, process::id()));
+    fs::write(&path, rom).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8_dasm")).args(args).arg(&path).output();
+    fs::remove_file(&path).unwrap();
+    let output = output.unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// LD V0, 0x0A / LD F, V0 / DRW V0, V0, 5 / JMP 0x206 / one row of sprite data
+const ROM: [u8; 10] = [0x60, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06, 0xF0, 0x90];
+
+#[test]
+fn lists_whole_rom() {
+    let expected = "\
+200: 600A  LD V0, 0x0A
+202: F029  LD F, V0
+204: D005  DRW V0, V0, 5
+206: 1206  JMP 0x206
+208: F090  DB 0xF090
+";
+    assert_eq!(run_dasm("whole", &ROM, &[]), expected);
+}
+
+#[test]
+fn lists_a_range_with_raw_bits() {
+    let expected = "\
+206: 1206  JMP 0x206          ...#..#......##.
+208: F090  DB 0xF090          ####....#..#....
+";
+    assert_eq!(run_dasm("range", &ROM, &["--start", "0x206", "--length", "4", "--raw"]), expected);
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    let status =
+        Command::new(env!("CARGO_BIN_EXE_chip8_dasm")).arg("--start").output().unwrap().status;
+    assert!(!status.success());
+}
@@ -1,3 +1,184 @@
+// SDL2 frontend - opens a window, runs the ROM given on the command line at 60 frames a second
+// and beeps while the sound timer is running. Keys use the usual COSMAC layout on the left of
+// the keyboard (1234 / QWER / ASDF / ZXCV), Escape quits
+use std::env;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chip8_core::{Chip8Error, Emu, Keypad, DEFAULT_BEEP_HZ, SCREEN_HEIGHT, SCREEN_WIDTH};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::{AudioSubsystem, Sdl};
+
+// Every CHIP-8 pixel becomes a SCALE x SCALE block, hires games are drawn at half that
+const SCALE: u32 = 10;
+const WINDOW_WIDTH: u32 = SCREEN_WIDTH as u32 * SCALE;
+const WINDOW_HEIGHT: u32 = SCREEN_HEIGHT as u32 * SCALE;
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+const ON_COLOR: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const OFF_COLOR: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: {} path/to/game", args[0]);
+        process::exit(1);
+    }
+
+    let mut emu = Emu::new();
+    if let Err(err) = emu.load_rom_from_path(&args[1]) {
+        eprintln!("Couldn't load {}: {}", args[1], err);
+        process::exit(1);
+    }
+
+    if let Err(err) = run(&mut emu) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run(emu: &mut Emu) -> Result<(), String> {
+    let sdl = sdl2::init()?;
+    let mut canvas = open_window(&sdl)?;
+    let beeper = open_beeper(&sdl.audio()?)?;
+    let texture_creator = canvas.texture_creator();
+    let keypad = Keypad::default();
+    let mut event_pump = sdl.event_pump()?;
+
+    // the texture is sized to the current resolution so SCHIP hires mode just gets scaled
+    // down into the same window, and it's only recreated when the resolution changes
+    let new_texture = |(width, height): (u32, u32)| {
+        texture_creator
+            .create_texture_streaming(PixelFormatEnum::ABGR8888, width, height)
+            .map_err(|err| err.to_string())
+    };
+    let mut size = (emu.display_width() as u32, emu.display_height() as u32);
+    let mut texture = new_texture(size)?;
+
+    loop {
+        let frame_start = Instant::now();
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    return Ok(());
+                }
+                Event::KeyDown { keycode: Some(key), .. } => {
+                    if let Some(physical) = physical_key(key) {
+                        keypad.press(emu, physical);
+                    }
+                }
+                Event::KeyUp { keycode: Some(key), .. } => {
+                    if let Some(physical) = physical_key(key) {
+                        keypad.release(emu, physical);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        match emu.run_frame() {
+            Ok(()) => (),
+            // 00FD - the game asked to quit
+            Err(Chip8Error::Halted) => return Ok(()),
+            Err(err) => return Err(format!("Emulation stopped at {:#05X}: {}", emu.pc(), err)),
+        }
+
+        if emu.is_beeping() {
+            beeper.resume();
+        } else {
+            beeper.pause();
+        }
+
+        let current = (emu.display_width() as u32, emu.display_height() as u32);
+        if current != size {
+            size = current;
+            texture = new_texture(size)?;
+        }
+        let pixels = emu.render_rgba(ON_COLOR, OFF_COLOR);
+        texture.update(None, &pixels, size.0 as usize * 4).map_err(|err| err.to_string())?;
+        canvas.copy(&texture, None, None)?;
+        canvas.present();
+
+        if let Some(remaining) = FRAME_TIME.checked_sub(frame_start.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+}
+
+fn open_window(sdl: &Sdl) -> Result<Canvas<Window>, String> {
+    let video = sdl.video()?;
+    let window = video
+        .window("Chip-8 Emulator", WINDOW_WIDTH, WINDOW_HEIGHT)
+        .position_centered()
+        .build()
+        .map_err(|err| err.to_string())?;
+    let mut canvas = window.into_canvas().build().map_err(|err| err.to_string())?;
+    canvas.clear();
+    canvas.present();
+    Ok(canvas)
+}
+
+// SDL keycodes for letters and digits are their lowercase ASCII codes, which is what Keypad
+// expects. Anything else isn't on the keypad
+fn physical_key(key: Keycode) -> Option<u8> {
+    u8::try_from(key.into_i32()).ok()
+}
+
+// Square wave played by SDL's audio thread, the device is paused and resumed to start and
+// stop the beep
+struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = if self.phase < 0.5 { self.volume } else { -self.volume };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+fn open_beeper(audio: &AudioSubsystem) -> Result<AudioDevice<SquareWave>, String> {
+    let spec = AudioSpecDesired { freq: Some(44_100), channels: Some(1), samples: None };
+    audio.open_playback(None, &spec, |spec| SquareWave {
+        phase_inc: DEFAULT_BEEP_HZ / spec.freq as f32,
+        phase: 0.0,
+        volume: 0.25,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_maps_to_keypad() {
+        let keypad = Keypad::default();
+        let key = |code| physical_key(code).and_then(|physical| keypad.key_for(physical));
+        assert_eq!(key(Keycode::Num1), Some(0x1));
+        assert_eq!(key(Keycode::Q), Some(0x4));
+        assert_eq!(key(Keycode::V), Some(0xF));
+        assert_eq!(key(Keycode::P), None);
+        assert_eq!(physical_key(Keycode::Left), None);
+    }
+
+    #[test]
+    fn opens_headless_window() {
+        // the dummy driver needs no display, so this also runs on CI
+        env::set_var("SDL_VIDEODRIVER", "dummy");
+        let sdl = sdl2::init().unwrap();
+        let canvas = open_window(&sdl).unwrap();
+        assert_eq!(canvas.output_size().unwrap(), (WINDOW_WIDTH, WINDOW_HEIGHT));
+    }
 }
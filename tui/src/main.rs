@@ -0,0 +1,248 @@
+// Terminal frontend for SSH sessions and machines without a display. Every character cell
+// holds two pixels stacked with half-block characters, so the 64x32 screen fits in 64x16 cells
+//
+//   chip8_tui [--scale N] [--palette white|green|amber] path/to/game
+//
+// Keys use the usual COSMAC layout on the left of the keyboard, Escape quits
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+use chip8_core::{Chip8Error, Emu, Keypad};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// Most terminals only report key presses, so a key counts as held until it hasn't repeated
+// for this long
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Options<'a> {
+    scale: usize,
+    // (on, off) colours
+    palette: (Color, Color),
+    rom: &'a str,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = match parse_args(&args[1..]) {
+        Some(options) => options,
+        None => {
+            eprintln!("Usage: {} [--scale N] [--palette white|green|amber] path/to/game", args[0]);
+            process::exit(1);
+        }
+    };
+
+    let mut emu = Emu::new();
+    if let Err(err) = emu.load_rom_from_path(options.rom) {
+        eprintln!("Couldn't load {}: {}", options.rom, err);
+        process::exit(1);
+    }
+
+    if let Err(err) = run(&mut emu, &options) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String]) -> Option<Options<'_>> {
+    let mut scale = 1;
+    let mut palette = (Color::White, Color::Black);
+    let mut rom = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scale" => scale = args.next()?.parse().ok().filter(|&scale| scale > 0)?,
+            "--palette" => {
+                palette = match args.next()?.as_str() {
+                    "white" => (Color::White, Color::Black),
+                    "green" => (Color::Green, Color::Black),
+                    "amber" => (Color::Rgb { r: 0xFF, g: 0xB0, b: 0x00 }, Color::Black),
+                    _ => return None,
+                }
+            }
+            path if rom.is_none() && !path.starts_with("--") => rom = Some(path),
+            _ => return None,
+        }
+    }
+    Some(Options { scale, palette, rom: rom? })
+}
+
+fn run(emu: &mut Emu, options: &Options) -> Result<(), String> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode().map_err(|err| err.to_string())?;
+    // key release events where the terminal supports them, otherwise keys time out
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if releases {
+        let flags = KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+        execute!(stdout, PushKeyboardEnhancementFlags(flags)).map_err(|err| err.to_string())?;
+    }
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)
+        .map_err(|err| err.to_string())?;
+
+    let result = emulate(emu, options, releases, &mut stdout);
+
+    // put the terminal back even if the emulator stopped with an error
+    if releases {
+        let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(stdout, ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result
+}
+
+fn emulate(
+    emu: &mut Emu,
+    options: &Options,
+    releases: bool,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let keypad = Keypad::default();
+    let mut last_pressed: [Option<Instant>; 16] = [None; 16];
+    let mut drawn_size = (0, 0);
+    let mut last_step = Instant::now();
+
+    loop {
+        while event::poll(Duration::ZERO).map_err(|err| err.to_string())? {
+            let Event::Key(KeyEvent { code, kind, .. }) =
+                event::read().map_err(|err| err.to_string())?
+            else {
+                continue;
+            };
+            let physical = match code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char(c) if c.is_ascii() => c as u8,
+                _ => continue,
+            };
+            if kind == KeyEventKind::Release {
+                keypad.release(emu, physical);
+            } else if keypad.press(emu, physical) {
+                if let Some(key) = keypad.key_for(physical) {
+                    last_pressed[key as usize] = Some(Instant::now());
+                }
+            }
+        }
+        if !releases {
+            for (key, pressed) in last_pressed.iter_mut().enumerate() {
+                if pressed.is_some_and(|at| at.elapsed() > KEY_HOLD) {
+                    emu.keypress(key, false);
+                    *pressed = None;
+                }
+            }
+        }
+
+        let now = Instant::now();
+        match emu.step(now - last_step) {
+            Ok(()) => (),
+            // 00FD - the game asked to quit
+            Err(Chip8Error::Halted) => return Ok(()),
+            Err(err) => return Err(format!("Emulation stopped at {:#05X}: {}", emu.pc(), err)),
+        }
+        last_step = now;
+
+        draw(emu, options, &mut drawn_size, out).map_err(|err| err.to_string())?;
+        std::thread::sleep(FRAME_TIME.saturating_sub(now.elapsed()));
+    }
+}
+
+// Redraws the terminal rows covering the pixel rows that changed, or everything after a
+// resolution switch
+fn draw(
+    emu: &mut Emu,
+    options: &Options,
+    drawn_size: &mut (usize, usize),
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let dirty = emu.take_dirty_rows();
+    let size = (emu.display_width(), emu.display_height());
+    let lines = render_lines(emu, options.scale);
+    let mut rows: Vec<usize> = if size != *drawn_size {
+        *drawn_size = size;
+        queue!(out, ResetColor, terminal::Clear(terminal::ClearType::All))?;
+        (0..lines.len()).collect()
+    } else {
+        // pixel row y covers half-rows y * scale up to (y + 1) * scale, two to a terminal row
+        dirty
+            .iter()
+            .flat_map(|&y| (y * options.scale / 2)..=(((y + 1) * options.scale - 1) / 2))
+            .collect()
+    };
+    rows.dedup();
+
+    let (on, off) = options.palette;
+    queue!(out, SetForegroundColor(on), SetBackgroundColor(off))?;
+    for row in rows {
+        queue!(out, cursor::MoveTo(0, row as u16), Print(&lines[row]))?;
+    }
+    queue!(out, ResetColor)?;
+    out.flush()
+}
+
+// The screen as terminal lines, each pixel `scale` cells wide and `scale` half-cells tall
+fn render_lines(emu: &Emu, scale: usize) -> Vec<String> {
+    let (width, height) = (emu.display_width() * scale, emu.display_height() * scale);
+    let pixel = |x: usize, half_row: usize| {
+        half_row < height && emu.get_pixel(x / scale, half_row / scale)
+    };
+    (0..height.div_ceil(2))
+        .map(|row| {
+            (0..width)
+                .map(|x| match (pixel(x, row * 2), pixel(x, row * 2 + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn frame_dimensions() {
+        let mut emu = Emu::new();
+        // LD V0, 0x00 / LD F, V0 / DRW V0, V0, 5
+        emu.load_rom(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05]).unwrap();
+        emu.tick_n(3).unwrap();
+
+        let lines = render_lines(&emu, 1);
+        assert_eq!(lines.len(), 16);
+        assert!(lines.iter().all(|line| line.chars().count() == 64));
+        // the 0 glyph is F0 90 90 90 F0, two rows to a cell
+        assert!(lines[0].starts_with("█▀▀█ "));
+        assert!(lines[1].starts_with("█  █ "));
+        assert!(lines[2].starts_with("▀▀▀▀ "));
+
+        let lines = render_lines(&emu, 3);
+        assert_eq!(lines.len(), 48);
+        assert!(lines.iter().all(|line| line.chars().count() == 192));
+    }
+
+    #[test]
+    fn parses_options() {
+        let full = args(&["--scale", "2", "--palette", "green", "pong.ch8"]);
+        assert_eq!(
+            parse_args(&full),
+            Some(Options { scale: 2, palette: (Color::Green, Color::Black), rom: "pong.ch8" })
+        );
+        assert_eq!(parse_args(&args(&["pong.ch8"])).map(|options| options.scale), Some(1));
+        assert_eq!(parse_args(&args(&[])), None);
+        assert_eq!(parse_args(&args(&["--scale", "0", "pong.ch8"])), None);
+        assert_eq!(parse_args(&args(&["--palette", "pink", "pong.ch8"])), None);
+    }
+}
@@ -0,0 +1,109 @@
+// wasm-bindgen wrapper so the emulator can run in a browser. www/ has a page that draws
+// display() onto a canvas and feeds keyboard events to key_down/key_up. Errors reach JS as
+// their Display message since Chip8Error isn't a JS type
+use chip8_core::{Chip8Error, Emu, Keypad};
+use wasm_bindgen::prelude::*;
+
+const ON_COLOR: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const OFF_COLOR: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+#[wasm_bindgen]
+pub struct EmuWasm {
+    emu: Emu,
+    keypad: Keypad,
+}
+
+#[wasm_bindgen]
+impl EmuWasm {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self { emu: Emu::new(), keypad: Keypad::default() }
+    }
+
+    // Resets the machine and loads a ROM from a Uint8Array
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), String> {
+        self.emu.reset();
+        self.emu.load_rom(data).map_err(to_js_error)
+    }
+
+    // Runs one instruction and returns its opcode
+    pub fn tick(&mut self) -> Result<u16, String> {
+        self.emu.tick().map_err(to_js_error)
+    }
+
+    // Runs one 60Hz frame, call this from requestAnimationFrame
+    pub fn run_frame(&mut self) -> Result<(), String> {
+        self.emu.run_frame().map_err(to_js_error)
+    }
+
+    // `key` is a character code from a KeyboardEvent, mapped through the default COSMAC layout
+    pub fn key_down(&mut self, key: u8) {
+        self.keypad.press(&mut self.emu, key);
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        self.keypad.release(&mut self.emu, key);
+    }
+
+    // RGBA pixels for the current screen, ready for an ImageData of width() x height()
+    pub fn display(&self) -> Vec<u8> {
+        self.emu.render_rgba(ON_COLOR, OFF_COLOR)
+    }
+
+    pub fn width(&self) -> usize {
+        self.emu.display_width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.emu.display_height()
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.emu.is_beeping()
+    }
+}
+
+fn to_js_error(err: Chip8Error) -> String {
+    err.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_and_tick() {
+        let mut emu = EmuWasm::new();
+        // LD V0, 0x0A / LD F, V0 / DRW V0, V0, 5
+        emu.load_rom(&[0x60, 0x0A, 0xF0, 0x29, 0xD0, 0x05]).unwrap();
+        assert_eq!(emu.tick(), Ok(0x600A));
+        emu.run_frame().unwrap();
+
+        let pixels = emu.display();
+        assert_eq!(pixels.len(), emu.width() * emu.height() * 4);
+        // the A glyph's top row starts lit at (10, 10)
+        let lit = (10 * emu.width() + 10) * 4;
+        assert_eq!(pixels[lit..lit + 4], ON_COLOR);
+        assert_eq!(pixels[..4], OFF_COLOR);
+    }
+
+    #[test]
+    fn keys_use_the_default_layout() {
+        let mut emu = EmuWasm::new();
+        // LD V0, K
+        emu.load_rom(&[0xF0, 0x0A]).unwrap();
+        emu.tick().unwrap();
+        emu.key_down(b'w');
+        emu.tick().unwrap();
+        assert_eq!(emu.emu.v_reg(0), 0x5);
+        emu.key_up(b'w');
+    }
+
+    #[test]
+    fn errors_become_strings() {
+        let mut emu = EmuWasm::new();
+        let err = emu.load_rom(&[0; 4096]).unwrap_err();
+        assert_eq!(err, "ROM is 4096 bytes but only 3584 fit in RAM");
+    }
+}
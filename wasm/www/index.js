@@ -0,0 +1,57 @@
+// Build the bindings with `wasm-pack build --target web --out-dir www/pkg` from the wasm crate,
+// then serve this directory with any static file server
+import init, { EmuWasm } from "./pkg/chip8_wasm.js";
+
+const canvas = document.getElementById("screen");
+const ctx = canvas.getContext("2d");
+
+await init();
+const emu = new EmuWasm();
+let frame = null;
+
+// single character keys only, Keypad maps their character codes to the CHIP-8 keys
+function keyCode(evt) {
+    return evt.key.length === 1 ? evt.key.charCodeAt(0) : null;
+}
+
+document.addEventListener("keydown", (evt) => {
+    const code = keyCode(evt);
+    if (code !== null && code < 256) emu.key_down(code);
+});
+
+document.addEventListener("keyup", (evt) => {
+    const code = keyCode(evt);
+    if (code !== null && code < 256) emu.key_up(code);
+});
+
+document.getElementById("rom").addEventListener("change", async (evt) => {
+    const file = evt.target.files[0];
+    if (!file) return;
+    if (frame !== null) cancelAnimationFrame(frame);
+    frame = null;
+    try {
+        emu.load_rom(new Uint8Array(await file.arrayBuffer()));
+    } catch (err) {
+        console.log("Couldn't load ROM:", err);
+        return;
+    }
+    frame = requestAnimationFrame(loop);
+});
+
+function loop() {
+    try {
+        emu.run_frame();
+    } catch (err) {
+        console.log("Emulation stopped:", err);
+        return;
+    }
+    const width = emu.width();
+    const height = emu.height();
+    if (canvas.width !== width || canvas.height !== height) {
+        canvas.width = width;
+        canvas.height = height;
+    }
+    const pixels = new Uint8ClampedArray(emu.display());
+    ctx.putImageData(new ImageData(pixels, width, height), 0, 0);
+    frame = requestAnimationFrame(loop);
+}